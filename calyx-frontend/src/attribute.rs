@@ -79,6 +79,17 @@ pub enum BoolAttr {
     #[strum(serialize = "protected")]
     /// Indicate that the cell should not be removed or shared during optimization.
     Protected,
+    #[strum(serialize = "fsm_distinct")]
+    /// Never share the FSM of this static group with another static group.
+    FSMDistinct,
+    #[strum(serialize = "exclusive_threads")]
+    /// The threads of this `par` never run at the same time, so their static
+    /// groups may share FSMs.
+    ExclusiveThreads,
+    #[strum(serialize = "if_ctrl")]
+    /// Denotes a group that was generated from a `static if` by compile-static.
+    /// The static groups it enables are the branches of the `if`.
+    IfCtrl,
 }
 
 impl From<BoolAttr> for Attribute {
@@ -127,6 +138,9 @@ pub enum NumAttr {
     /// dynamic.
     /// Therefore, we only place if we can *guarantee* the interval of the component.
     Interval,
+    #[strum(serialize = "fsm_group")]
+    /// Static groups with the same `fsm_group` value share an FSM.
+    FSMGroup,
 }
 impl From<NumAttr> for Attribute {
     fn from(attr: NumAttr) -> Self {
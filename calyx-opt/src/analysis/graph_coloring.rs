@@ -1,6 +1,7 @@
 use calyx_utils::{Idx, WeightGraph};
 use itertools::Itertools;
 use petgraph::algo;
+use petgraph::visit::IntoEdgeReferences;
 use std::{
     collections::{BTreeMap, HashMap},
     fmt::Display,
@@ -48,6 +49,34 @@ where
         self.graph.add_all_edges(items)
     }
 
+    /// Returns true if there is a conflict edge between `a` and `b`.
+    pub fn has_conflict(&self, a: &T, b: &T) -> bool {
+        self.graph
+            .graph
+            .has_edge(self.graph.index_map[a], self.graph.index_map[b])
+    }
+
+    /// Returns the conflict graph obtained by replacing each node `n` with
+    /// `rep(n)`, so that all nodes with the same representative get the same
+    /// color. Conflicts between nodes with the same representative are
+    /// dropped.
+    pub fn merge_nodes<F>(&self, rep: F) -> GraphColoring<T>
+    where
+        F: Fn(&T) -> T,
+    {
+        let rev_map = self.graph.reverse_index();
+        let mut merged =
+            GraphColoring::from(self.graph.nodes().map(&rep).sorted().dedup());
+        for (a_idx, b_idx, _) in self.graph.graph.edge_references() {
+            let rep_a = rep(&rev_map[&a_idx]);
+            let rep_b = rep(&rev_map[&b_idx]);
+            if rep_a != rep_b {
+                merged.insert_conflict(&rep_a, &rep_b);
+            }
+        }
+        merged
+    }
+
     pub fn has_nodes(&self) -> bool {
         self.graph.graph.node_count() > 0
     }
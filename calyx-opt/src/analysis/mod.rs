@@ -43,7 +43,7 @@ pub use promotion_analysis::PromotionAnalysis;
 pub use read_write_set::{AssignmentAnalysis, ReadWriteSet};
 pub use schedule_conflicts::ScheduleConflicts;
 pub use share_set::ShareSet;
-pub use static_fsm::{FSMEncoding, StaticFSM};
+pub use static_fsm::{EncodingChoice, FSMEncoding, StaticFSM};
 pub use static_par_timing::StaticParTiming;
 pub use static_tree::{Node, ParNodes, SingleNode, StateType};
 pub use variable_detection::VariableDetection;
@@ -11,6 +11,35 @@ pub enum FSMEncoding {
     #[default]
     Binary,
     OneHot,
+    /// Binary-reflected Gray code: consecutive states differ by one bit.
+    Gray,
+}
+
+#[derive(Debug, Clone, Copy)]
+/// Determines which encoding a static FSM should get.
+pub enum EncodingChoice {
+    /// Use one-hot encoding if the number of states is at most the cutoff,
+    /// and binary encoding otherwise. Gray encoding is never chosen, since
+    /// its logic takes a comparator per state.
+    Auto { one_hot_cutoff: u64 },
+    /// Always use the given encoding, regardless of the number of states.
+    Fixed(FSMEncoding),
+}
+
+impl EncodingChoice {
+    /// Chooses the encoding for an FSM that has `num_states` states.
+    pub fn choose_encoding(&self, num_states: u64) -> FSMEncoding {
+        match self {
+            EncodingChoice::Auto { one_hot_cutoff } => {
+                if num_states > *one_hot_cutoff {
+                    FSMEncoding::Binary
+                } else {
+                    FSMEncoding::OneHot
+                }
+            }
+            EncodingChoice::Fixed(encoding) => *encoding,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -18,30 +47,38 @@ pub enum FSMEncoding {
 pub struct StaticFSM {
     /// The actual register cell
     fsm_cell: ir::RRC<ir::Cell>,
-    /// Type of encoding (binary or one-hot)
+    /// Type of encoding (binary, one-hot, or gray)
     encoding: FSMEncoding,
+    /// Number of states the fsm counts through (needed to enumerate the
+    /// gray-code transitions).
+    num_states: u64,
     /// The fsm's bitwidth (this redundant information bc  we have `cell`)
     /// but makes it easier if we easily have access to this.
     bitwidth: u64,
     /// Mapping of queries: (u64, u64) -> Port
     queries: HashMap<(u64, u64), ir::RRC<ir::Port>>,
+    /// Whether binary queries are also built once into a wire and shared
+    /// (one-hot queries always are).
+    share_queries: bool,
+    /// The incrementer (adder or shifter) for the fsm, once it has been built.
+    /// Since the increment logic is the same for every user of the fsm, all
+    /// of them share it.
+    incrementer: Option<ir::RRC<ir::Cell>>,
 }
 impl StaticFSM {
     // Builds a static_fsm from: num_states and encoding type.
+    // If `share_queries` is set, binary queries are memoized like one-hot ones.
     pub fn from_basic_info(
         num_states: u64,
         encoding: FSMEncoding,
+        share_queries: bool,
         builder: &mut ir::Builder,
     ) -> Self {
         // Determine number of bits needed in the register.
-        let fsm_size = match encoding {
-            /* represent 0..latency */
-            FSMEncoding::Binary => get_bit_width_from(num_states + 1),
-            FSMEncoding::OneHot => num_states,
-        };
+        let fsm_size = Self::required_bitwidth(num_states, encoding);
         // OHE needs an initial value of 1.
         let register = match encoding {
-            FSMEncoding::Binary => {
+            FSMEncoding::Binary | FSMEncoding::Gray => {
                 builder.add_primitive("fsm", "std_reg", &[fsm_size])
             }
             FSMEncoding::OneHot => {
@@ -51,32 +88,92 @@ impl StaticFSM {
 
         StaticFSM {
             encoding,
+            num_states,
             fsm_cell: register,
             bitwidth: fsm_size,
             queries: HashMap::new(),
+            share_queries,
+            incrementer: None,
+        }
+    }
+
+    // Returns the number of bits an fsm with `num_states` states needs
+    // under `encoding`.
+    pub fn required_bitwidth(num_states: u64, encoding: FSMEncoding) -> u64 {
+        match encoding {
+            /* represent 0..latency */
+            FSMEncoding::Binary | FSMEncoding::Gray => {
+                get_bit_width_from(num_states + 1)
+            }
+            FSMEncoding::OneHot => num_states,
         }
     }
 
+    // Returns the gray code of the `state`-th state.
+    fn gray_code(state: u64) -> u64 {
+        state ^ (state >> 1)
+    }
+
     // Builds an incrementer, and returns the assignments and incrementer cell itself.
     // assignments are:
     // adder.left = fsm.out; adder.right = 1;
+    // For gray encoding, the incrementer is a wire that holds the next state:
+    // gray_incr.in = fsm.out == gray(i) ? gray(i+1); (for each state i)
+    // The incrementer cell is only built the first time this is called: later
+    // calls (e.g., from other groups sharing this fsm) reuse it.
     // Returns tuple: (assignments, adder)
     pub fn build_incrementer(
-        &self,
+        &mut self,
         builder: &mut ir::Builder,
     ) -> (Vec<ir::Assignment<Nothing>>, ir::RRC<ir::Cell>) {
         let fsm_cell = Rc::clone(&self.fsm_cell);
         // For OHE, the "adder" can just be a shifter.
         // For OHE the first_state = 1 rather than 0.
         // Final state is encoded differently for OHE vs. Binary
-        let adder = match self.encoding {
-            FSMEncoding::Binary => {
-                builder.add_primitive("adder", "std_add", &[self.bitwidth])
-            }
-            FSMEncoding::OneHot => {
-                builder.add_primitive("lsh", "std_lsh", &[self.bitwidth])
+        let adder = match &self.incrementer {
+            Some(adder) => Rc::clone(adder),
+            None => {
+                let adder = match self.encoding {
+                    FSMEncoding::Binary => builder.add_primitive(
+                        "adder",
+                        "std_add",
+                        &[self.bitwidth],
+                    ),
+                    FSMEncoding::OneHot => builder.add_primitive(
+                        "lsh",
+                        "std_lsh",
+                        &[self.bitwidth],
+                    ),
+                    FSMEncoding::Gray => builder.add_primitive(
+                        "gray_incr",
+                        "std_wire",
+                        &[self.bitwidth],
+                    ),
+                };
+                self.incrementer = Some(Rc::clone(&adder));
+                adder
             }
         };
+        if matches!(self.encoding, FSMEncoding::Gray) {
+            // There's no cheap way to add one to a gray code, so we look up
+            // the next state with one assignment per state.
+            let incr_assigns = (0..self.num_states.saturating_sub(1))
+                .flat_map(|state| {
+                    let cur = builder
+                        .add_constant(Self::gray_code(state), self.bitwidth);
+                    let next = builder.add_constant(
+                        Self::gray_code(state + 1),
+                        self.bitwidth,
+                    );
+                    let in_state = guard!(fsm_cell["out"] == cur["out"]);
+                    build_assignments!(
+                      builder;
+                      adder["in"] = in_state ? next["out"];
+                    )
+                })
+                .collect();
+            return (incr_assigns, adder);
+        }
         let const_one = builder.add_constant(1, self.bitwidth);
         let incr_assigns = build_assignments!(
           builder;
@@ -125,7 +222,9 @@ impl StaticFSM {
         let fsm_cell = Rc::clone(&self.fsm_cell);
         let signal_on = builder.add_constant(1, 1);
         let const_0 = match self.encoding {
-            FSMEncoding::Binary => builder.add_constant(0, self.bitwidth),
+            FSMEncoding::Binary | FSMEncoding::Gray => {
+                builder.add_constant(0, self.bitwidth)
+            }
             FSMEncoding::OneHot => builder.add_constant(1, self.bitwidth),
         };
         let assigns = build_assignments!(
@@ -151,17 +250,34 @@ impl StaticFSM {
             return Box::new(g);
         }
 
-        if beg + 1 == end {
+        // If we share queries and have already built this one, reuse its wire.
+        if self.share_queries {
+            if let Some(port) = self.queries.get(&(beg, end)) {
+                return Box::new(ir::Guard::port(Rc::clone(port)));
+            }
+        }
+
+        let is_gray = matches!(self.encoding, FSMEncoding::Gray);
+        let g: ir::Guard<Nothing> = if is_gray {
+            // Gray codes of consecutive states aren't ordered, so check each
+            // state in the interval separately. This takes a comparator per
+            // state, which is why `EncodingChoice::Auto` never picks gray.
+            (beg..end)
+                .map(|state| {
+                    let state_const = builder
+                        .add_constant(Self::gray_code(state), self.bitwidth);
+                    guard!(fsm_cell["out"] == state_const["out"])
+                })
+                .reduce(|acc, g| acc.or(g))
+                .expect("query should contain at least one state")
+        } else if beg + 1 == end {
             // if beg + 1 == end then we only need to check if fsm == beg
             let interval_const = builder.add_constant(beg, self.bitwidth);
-            let g = guard!(fsm_cell["out"] == interval_const["out"]);
-            Box::new(g)
+            guard!(fsm_cell["out"] == interval_const["out"])
         } else if beg == 0 {
             // if beg == 0, then we only need to check if fsm < end
             let end_const = builder.add_constant(end, self.bitwidth);
-            let lt: ir::Guard<Nothing> =
-                guard!(fsm_cell["out"] < end_const["out"]);
-            Box::new(lt)
+            guard!(fsm_cell["out"] < end_const["out"])
         } else {
             // otherwise, check if fsm >= beg & fsm < end
             let beg_const = builder.add_constant(beg, self.bitwidth);
@@ -170,8 +286,37 @@ impl StaticFSM {
                 guard!(fsm_cell["out"] >= beg_const["out"]);
             let end_guard: ir::Guard<Nothing> =
                 guard!(fsm_cell["out"] < end_const["out"]);
-            Box::new(ir::Guard::And(Box::new(beg_guard), Box::new(end_guard)))
+            ir::Guard::And(Box::new(beg_guard), Box::new(end_guard))
+        };
+        if !self.share_queries {
+            return Box::new(g);
         }
+
+        // Otherwise, build a wire that holds the result of the comparison so
+        // that every use of this query shares the same comparators.
+        let port = Self::build_binary_query(g, (beg, end), builder);
+        self.queries.insert((beg, end), Rc::clone(&port));
+        Box::new(ir::Guard::port(port))
+    }
+
+    // Given a (beg, end) query and the guard `g` that checks it, returns a
+    // `port`: port is a `wire.out`, where `wire` is driven high exactly when
+    // `g` holds, i.e., when the FSM really is between [beg, end).
+    fn build_binary_query(
+        g: ir::Guard<Nothing>,
+        (beg, end): (u64, u64),
+        builder: &mut ir::Builder,
+    ) -> ir::RRC<ir::Port> {
+        let formatted_name = format!("bw_{}_{}", beg, end);
+        let wire: ir::RRC<ir::Cell> =
+            builder.add_primitive(formatted_name, "std_wire", &[1]);
+        let wire_out = wire.borrow().get("out");
+        let signal_on = builder.add_constant(1, 1);
+        let assigns = build_assignments!(builder;
+            wire["in"] = g ? signal_on["out"];
+        );
+        builder.add_continuous_assignments(assigns.to_vec());
+        wire_out
     }
 
     // Given a one-hot query, it will return a guard corresponding to that query.
@@ -218,7 +363,8 @@ impl StaticFSM {
         // Continuous assignments to check the FSM
         let assigns = {
             let in_width = fsm_bitwidth;
-            // Since 00...00 is the initial state, we need to check lb-1.
+            // The register is initialized to 00...01 (state 0 is bit 0), so
+            // state i is bit i and the query checks bits [lb, ub).
             let start_index = lb;
             // Since verilog slices are inclusive.
             let end_index = ub - 1;
@@ -250,4 +396,9 @@ impl StaticFSM {
     pub fn get_bitwidth(&self) -> u64 {
         self.bitwidth
     }
+
+    // Return the number of states the FSM counts through
+    pub fn get_num_states(&self) -> u64 {
+        self.num_states
+    }
 }
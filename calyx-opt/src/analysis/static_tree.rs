@@ -1,9 +1,9 @@
-use super::{FSMEncoding, StaticFSM};
+use super::{EncodingChoice, StaticFSM};
 use calyx_ir::{self as ir};
 use calyx_ir::{build_assignments, Nothing};
 use calyx_ir::{guard, structure};
 use itertools::Itertools;
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::ops::Not;
 use std::rc::Rc;
 
@@ -61,7 +61,8 @@ impl Node {
             ir::Id,
             (OptionalStaticFSM, OptionalStaticFSM),
         >,
-        one_hot_cutoff: u64,
+        encoding_choice: EncodingChoice,
+        share_queries: bool,
     ) {
         match self {
             Node::Single(single_node) => single_node.instantiate_fsms(
@@ -69,14 +70,16 @@ impl Node {
                 coloring,
                 colors_to_max_values,
                 colors_to_fsm,
-                one_hot_cutoff,
+                encoding_choice,
+                share_queries,
             ),
             Node::Par(par_nodes) => par_nodes.instantiate_fsms(
                 builder,
                 coloring,
                 colors_to_max_values,
                 colors_to_fsm,
-                one_hot_cutoff,
+                encoding_choice,
+                share_queries,
             ),
         }
     }
@@ -84,18 +87,23 @@ impl Node {
     /// Count to n. Need to call `instantiate_fsms` before calling `count_to_n`.
     /// The equivalent methods for the two variants contain more implementation
     /// details.
-    /// `incr_start_cond` can optionally guard the 0->1 transition.
+    /// `incr_start_cond` can optionally guard the 0->1 transition. Nodes with a
+    /// latency of 1 have no 0->1 transition, so the condition guards the
+    /// transition out of their first iteration instead (if they repeat).
+    /// `reset_guard` can optionally force all of the FSMs back to their first
+    /// state (e.g., to abort a static island early).
     pub fn count_to_n(
         &mut self,
         builder: &mut ir::Builder,
         incr_start_cond: Option<ir::Guard<Nothing>>,
+        reset_guard: Option<ir::Guard<Nothing>>,
     ) {
         match self {
             Node::Single(single_node) => {
-                single_node.count_to_n(builder, incr_start_cond)
+                single_node.count_to_n(builder, incr_start_cond, reset_guard)
             }
             Node::Par(par_nodes) => {
-                par_nodes.count_to_n(builder, incr_start_cond)
+                par_nodes.count_to_n(builder, incr_start_cond, reset_guard)
             }
         }
     }
@@ -156,6 +164,30 @@ impl Node {
             Node::Par(par_nodes) => par_nodes.query_between(query, builder),
         }
     }
+
+    /// Returns a guard that is true during the first cycle of the node's
+    /// execution.
+    pub fn first_state_guard(
+        &mut self,
+        builder: &mut ir::Builder,
+    ) -> ir::Guard<Nothing> {
+        match self {
+            Node::Single(single_node) => single_node.first_state_guard(builder),
+            Node::Par(par_nodes) => par_nodes.first_state_guard(builder),
+        }
+    }
+
+    /// Returns a guard that is true during the last cycle of the node's
+    /// execution, i.e., the last cycle of its last iteration.
+    pub fn final_state_guard(
+        &mut self,
+        builder: &mut ir::Builder,
+    ) -> ir::Guard<Nothing> {
+        match self {
+            Node::Single(single_node) => single_node.final_state_guard(builder),
+            Node::Par(par_nodes) => par_nodes.final_state_guard(builder),
+        }
+    }
 }
 
 /// The following methods are used to help build the conflict graph for coloring
@@ -321,7 +353,7 @@ impl Node {
     }
 
     /// Get number of repeats.
-    fn get_num_repeats(&self) -> u64 {
+    pub fn get_num_repeats(&self) -> u64 {
         match self {
             Node::Single(single_node) => single_node.num_repeats,
             Node::Par(par_nodes) => par_nodes.num_repeats,
@@ -352,6 +384,10 @@ pub struct SingleNode {
     /// Note that you can build `fsm_schedule` from just this information,
     /// but it's convenient to have `fsm_schedule` avaialable.
     pub children: Vec<(Node, (u64, u64))>,
+    /// Whether the children are the branches of a `static if`. The branches
+    /// all execute during the entire latency of the node, but only one of
+    /// them actually runs, so they share an FSM instead of conflicting.
+    pub branch_children: bool,
     /// Keep track of where we are within a single iteration.
     /// If `latency` == 1, then we don't need an `fsm_cell`.
     pub fsm_cell: Option<ir::RRC<StaticFSM>>,
@@ -378,8 +414,9 @@ impl SingleNode {
     /// Note that it is not always necessary to instantiate one or both registers (e.g.,
     /// if num_repeats == 1 then you don't need an iter_count_register).
     ///
-    /// `one_hot_cutoff` is the cutoff to choose between binary and one hot encoding.
-    /// Any number of states greater than the cutoff will get binary encoding.
+    /// `encoding_choice` determines whether each register gets binary or
+    /// one hot encoding (see `EncodingChoice`), and `share_queries` whether
+    /// binary registers build each of their queries only once.
     fn instantiate_fsms(
         &mut self,
         builder: &mut ir::Builder,
@@ -389,7 +426,8 @@ impl SingleNode {
             ir::Id,
             (OptionalStaticFSM, OptionalStaticFSM),
         >,
-        one_hot_cutoff: u64,
+        encoding_choice: EncodingChoice,
+        share_queries: bool,
     ) {
         // Get color assigned to this node.
         let color = coloring.get(&self.root.0).expect("couldn't find group");
@@ -404,29 +442,23 @@ impl SingleNode {
                     .expect("Couldn't find color");
                 // Only need a `self.fsm_cell` if num_states > 1.
                 if *num_states != 1 {
-                    // Choose encoding based on one_hot_cutoff.
-                    let encoding = if *num_states > one_hot_cutoff {
-                        FSMEncoding::Binary
-                    } else {
-                        FSMEncoding::OneHot
-                    };
+                    let encoding = encoding_choice.choose_encoding(*num_states);
                     let fsm_cell = ir::rrc(StaticFSM::from_basic_info(
                         *num_states,
                         encoding,
+                        share_queries,
                         builder,
                     ));
                     self.fsm_cell = Some(fsm_cell);
                 }
                 // Only need a `self.iter_count_cell` if num_states > 1.
                 if *num_repeats != 1 {
-                    let encoding = if *num_repeats > one_hot_cutoff {
-                        FSMEncoding::Binary
-                    } else {
-                        FSMEncoding::OneHot
-                    };
+                    let encoding =
+                        encoding_choice.choose_encoding(*num_repeats);
                     let repeat_counter = ir::rrc(StaticFSM::from_basic_info(
                         *num_repeats,
                         encoding,
+                        share_queries,
                         builder,
                     ));
                     self.iter_count_cell = Some(repeat_counter);
@@ -457,7 +489,8 @@ impl SingleNode {
                 coloring,
                 colors_to_max_values,
                 colors_to_fsm,
-                one_hot_cutoff,
+                encoding_choice,
+                share_queries,
             );
         }
     }
@@ -465,10 +498,16 @@ impl SingleNode {
     /// Counts to n.
     /// If `incr_start_cond.is_some()`, then we will add it as an extra
     /// guard guarding the 0->1 transition.
+    /// If `reset_guard.is_some()`, then whenever it is high, the fsm and
+    /// iteration registers (of this node and all of its children) are reset
+    /// to their first state, regardless of their current value. These resets
+    /// are continuous assignments, since they must fire even when the
+    /// static island is not running.
     fn count_to_n(
         &mut self,
         builder: &mut ir::Builder,
         incr_start_cond: Option<ir::Guard<Nothing>>,
+        reset_guard: Option<ir::Guard<Nothing>>,
     ) {
         // res_vec will contain the assignments that count to n.
         let mut res_vec: Vec<ir::Assignment<Nothing>> = Vec::new();
@@ -535,8 +574,7 @@ impl SingleNode {
             ));
 
             // Reset the FSM when it is at its final fsm_state.
-            let final_fsm_state =
-                self.get_fsm_query((self.latency - 1, self.latency), builder);
+            let final_fsm_state = self.final_fsm_state(builder);
             res_vec.extend(
                 parent_fsm
                     .borrow_mut()
@@ -549,16 +587,41 @@ impl SingleNode {
         // greater than 1 if we're just offloading the computation for the entire time.
         // In this case, we still need the children to count to n.
         if self.latency > 1 {
+            // A child that is triggered over several intervals shares its
+            // registers across all of them, so we only make it count once.
+            let trigger_counts = self
+                .children
+                .iter()
+                .map(|(child, _)| child.get_group_name())
+                .counts();
+            let mut counted = HashSet::new();
             for (child, (beg, end)) in self.children.iter_mut() {
+                let name = child.get_group_name();
+                if !counted.insert(name) {
+                    continue;
+                }
                 // If beg == 0 and end > 1 then we need to "transfer" the incr_start_condition
                 // to the child so it guards the 0->1 transition.
                 let cond = if *beg == 0 && *end > 1 {
-                    incr_start_cond.clone()
+                    match (&incr_start_cond, &self.fsm_cell) {
+                        // If the child is triggered again later, the condition
+                        // should only hold it back while we are in our first
+                        // state.
+                        (Some(cond), Some(fsm))
+                            if trigger_counts[&name] > 1 =>
+                        {
+                            let in_first_state = *fsm
+                                .borrow_mut()
+                                .query_between(builder, (0, 1));
+                            Some(cond.clone().or(in_first_state.not()))
+                        }
+                        _ => incr_start_cond.clone(),
+                    }
                 } else {
                     None
                 };
                 // Recursively call `count_to_n`
-                child.count_to_n(builder, cond);
+                child.count_to_n(builder, cond, reset_guard.clone());
             }
         }
 
@@ -566,8 +629,7 @@ impl SingleNode {
         if self.num_repeats != 1 {
             // If self.latency == 10, then we should increment the self.iter_count_cell
             // each time fsm == 9, i.e., `final_fsm_state`.
-            let final_fsm_state =
-                self.get_fsm_query((self.latency - 1, self.latency), builder);
+            let final_fsm_state = self.final_fsm_state(builder);
 
             // `repeat_fsm` store number of iterations.
             let repeat_fsm = Rc::clone(
@@ -580,15 +642,29 @@ impl SingleNode {
                 repeat_fsm.borrow_mut().build_incrementer(builder);
             // We shouldn't increment `self.iter_count_cell` if we are in the final iteration:
             // we should reset it instead.
-            let final_repeat_state = *repeat_fsm.borrow_mut().query_between(
-                builder,
-                (self.num_repeats - 1, self.num_repeats),
-            );
+            let final_repeat_state = self.final_repeat_state(builder);
             let not_final_repeat_state = final_repeat_state.clone().not();
             res_vec.extend(repeat_adder_assigns);
+            let mut incr_guard =
+                final_fsm_state.clone().and(not_final_repeat_state);
+            // If there is no `self.fsm_cell` because the latency is 1, then
+            // the first iteration ends right away, so `incr_start_cond` has to
+            // guard the 0->1 transition of `self.iter_count_cell` instead.
+            if let (1, Some(g)) = (self.latency, incr_start_cond.clone()) {
+                let first_iter =
+                    *repeat_fsm.borrow_mut().query_between(builder, (0, 1));
+                // iter_count.in = iter_count == 0 & incr_start_cond ? iter_count + 1;
+                // iter_count.write_en = iter_count == 0 & incr_start_cond ? 1'd1;
+                res_vec.extend(repeat_fsm.borrow_mut().conditional_increment(
+                    first_iter.clone().and(g),
+                    Rc::clone(&repeat_adder),
+                    builder,
+                ));
+                incr_guard = incr_guard.and(first_iter.not());
+            }
             // Incrementing self.iter_count_cell when appropriate.
             res_vec.extend(repeat_fsm.borrow_mut().conditional_increment(
-                final_fsm_state.clone().and(not_final_repeat_state),
+                incr_guard,
                 repeat_adder,
                 builder,
             ));
@@ -599,6 +675,54 @@ impl SingleNode {
             ));
         }
 
+        // If there is a reset guard, then it takes priority over the counting
+        // logic: all other writes to the registers are disabled while it is
+        // high, and the registers are written back to their first state.
+        if let Some(reset) = reset_guard {
+            let registers = self
+                .fsm_cell
+                .iter()
+                .chain(self.iter_count_cell.iter())
+                .map(|fsm| fsm.borrow().get_unique_id())
+                .collect_vec();
+            for assign in res_vec.iter_mut() {
+                let dst_parent = assign.dst.borrow().get_parent_name();
+                if registers.contains(&dst_parent) {
+                    *assign.guard &= reset.clone().not();
+                }
+            }
+            // Only reset the registers this node actually counts with.
+            // The early reset group is not active while the component is
+            // being reset, so these have to be continuous assignments.
+            let mut reset_assigns = vec![];
+            if self.num_states > 1 {
+                if let Some(fsm) = &self.fsm_cell {
+                    reset_assigns.extend(
+                        fsm.borrow().conditional_reset(reset.clone(), builder),
+                    );
+                }
+            }
+            if self.num_repeats != 1 {
+                if let Some(repeat_fsm) = &self.iter_count_cell {
+                    reset_assigns.extend(
+                        repeat_fsm
+                            .borrow()
+                            .conditional_reset(reset.clone(), builder),
+                    );
+                }
+            }
+            // FSMs can be shared across static islands, so only add the
+            // resets that are not already there.
+            reset_assigns.retain(|asgn| {
+                !builder.component.continuous_assignments.iter().any(|a| {
+                    *a.dst.borrow() == *asgn.dst.borrow()
+                        && *a.src.borrow() == *asgn.src.borrow()
+                        && a.guard == asgn.guard
+                })
+            });
+            builder.add_continuous_assignments(reset_assigns);
+        }
+
         // Extend root assigns to include `res_vec` (which counts to n).
         self.root.1.extend(res_vec);
     }
@@ -672,8 +796,7 @@ impl SingleNode {
         // We shouldn't increment when we are in the final state
         // (we should be resetting instead).
         // So we need to `& !in_final_state` to the guard.
-        let final_fsm_state =
-            self.get_fsm_query((self.latency - 1, self.latency), builder);
+        let final_fsm_state = self.final_fsm_state(builder);
         let not_final_state = final_fsm_state.not();
 
         // However, if the final state is an offload state, then there's no need
@@ -737,12 +860,7 @@ impl SingleNode {
                     .borrow_mut()
                     .query_between(builder, (child_state, child_state + 1));
                 // now we need to check `child_fsm_in_final_state`
-                let total_child_latency =
-                    child.get_latency() * child.get_num_repeats();
-                let child_final_state = child.query_between(
-                    (total_child_latency - 1, total_child_latency),
-                    builder,
-                );
+                let child_final_state = child.final_state_guard(builder);
                 // Conditionally increment when `fsm==5 & child_final_state`
                 let parent_fsm_incr =
                     parent_fsm.borrow_mut().conditional_increment(
@@ -849,18 +967,23 @@ impl SingleNode {
             None => self.root.0,
             Some(fsm_rc) => fsm_rc.borrow().get_unique_id(),
         };
-        let total_latency = self.latency * self.num_repeats;
         fsm_info_map.insert(
             early_reset_group.borrow().name(),
             (
                 fsm_identifier,
-                self.query_between((0, 1), builder),
-                self.query_between((total_latency - 1, total_latency), builder),
+                self.first_state_guard(builder),
+                self.final_state_guard(builder),
             ),
         );
 
-        // Recursively realize each child.
+        // Recursively realize each child. A child that is triggered over
+        // several intervals is only realized once, since `count_to_n` only
+        // made the first of them count.
+        let mut realized = HashSet::new();
         self.children.iter_mut().for_each(|(child, _)| {
+            if !realized.insert(child.get_group_name()) {
+                return;
+            }
             child.realize(
                 ignore_timing_guards,
                 static_groups,
@@ -872,6 +995,43 @@ impl SingleNode {
         })
     }
 
+    // Returns a guard that is true during the first cycle of the node's
+    // execution.
+    fn first_state_guard(
+        &mut self,
+        builder: &mut ir::Builder,
+    ) -> ir::Guard<Nothing> {
+        self.query_between((0, 1), builder)
+    }
+
+    // Returns a guard that is true during the last cycle of the node's
+    // execution. This goes through `query_between`, so it works for every
+    // encoding and for registers that are shared with bigger nodes.
+    fn final_state_guard(
+        &mut self,
+        builder: &mut ir::Builder,
+    ) -> ir::Guard<Nothing> {
+        let total_latency = self.latency * self.num_repeats;
+        self.query_between((total_latency - 1, total_latency), builder)
+    }
+
+    // Returns a guard that is true during the last cycle of each iteration.
+    fn final_fsm_state(
+        &mut self,
+        builder: &mut ir::Builder,
+    ) -> ir::Guard<Nothing> {
+        self.get_fsm_query((self.latency - 1, self.latency), builder)
+    }
+
+    // Returns a guard that is true during the last iteration.
+    fn final_repeat_state(
+        &mut self,
+        builder: &mut ir::Builder,
+    ) -> ir::Guard<Nothing> {
+        *self
+            .get_repeat_query((self.num_repeats - 1, self.num_repeats), builder)
+    }
+
     // Rephrasing an (i,j) query: this breaks up the guard and makes it easier
     // to figure out what logic we need to instantiate to perform the query.
     // Restructure an (i,j) query into:
@@ -991,10 +1151,11 @@ impl SingleNode {
         if fsm_cell_opt.is_none() {
             // If there is no fsm cell even though latency > 1, then we must
             // have offloaded the entire latency. Therefore we just need
-            // to query the child.
+            // to query the child. (If the children are the branches of an
+            // `if`, they share an FSM, so querying the first one suffices.)
             assert!(self.offload_entire_latency());
-            let (only_child, _) = self.children.iter_mut().next().unwrap();
-            return only_child.query_between(query, builder);
+            let (first_child, _) = self.children.iter_mut().next().unwrap();
+            return first_child.query_between(query, builder);
         }
 
         let fsm_cell: Rc<std::cell::RefCell<StaticFSM>> =
@@ -1240,6 +1401,9 @@ impl SingleNode {
                 ignore_timing,
                 builder,
             ))),
+            // Comparisons and ports don't depend on timing, so they are kept
+            // as-is. Signed comparisons are done by cells like `std_slt`,
+            // so they show up here as the `Port` guard `slt.out`.
             ir::Guard::CompOp(op, l, r) => {
                 Box::new(ir::Guard::CompOp(op, l, r))
             }
@@ -1308,11 +1472,11 @@ impl SingleNode {
     // whether the tree offloads its entire latency, and returns the
     // appropriate `bool`.
     fn offload_entire_latency(&self) -> bool {
-        self.children.len() == 1
+        (self.children.len() == 1 || self.branch_children)
             && self
                 .children
                 .iter()
-                .any(|(_, (beg, end))| *beg == 0 && *end == self.latency)
+                .all(|(_, (beg, end))| *beg == 0 && *end == self.latency)
                 // This last check is prob unnecessary since it follows from the first two.
             && self.num_states == 1
     }
@@ -1343,6 +1507,10 @@ impl SingleNode {
             }
             child.add_conflicts(conflict_graph);
         }
+        // The branches of an `if` overlap, but never run at the same time.
+        if self.branch_children {
+            return;
+        }
         // Adding conflicts between overlapping children.
         for ((child_a, (beg_a, end_a)), (child_b, (beg_b, end_b))) in
             self.children.iter().tuple_combinations()
@@ -1378,6 +1546,38 @@ impl SingleNode {
         }
         cur_max
     }
+
+    /// Maps each state of this node's FSM to a readable label built from
+    /// `fsm_schedule`, e.g., for printing as comments next to the FSM.
+    /// A state that counts cycle `c` of group `A` is labeled `A%c`, and a
+    /// state that offloads cycles [i, j) to child `B` is labeled `A%[i:j] (B)`.
+    /// Cycles are counted within a single iteration of the group.
+    pub fn state_labels(&self) -> HashMap<u64, String> {
+        let name = self.root.0;
+        let mut labels = HashMap::new();
+        for ((beg, end), state_type) in &self.fsm_schedule {
+            match state_type {
+                StateType::Normal((i, j)) => {
+                    for (state, cycle) in (*i..*j).zip(*beg..*end) {
+                        labels.insert(state, format!("{name}%{cycle}"));
+                    }
+                }
+                StateType::Offload(state) => {
+                    let child = self
+                        .children
+                        .iter()
+                        .find(|(_, interval)| *interval == (*beg, *end))
+                        .map(|(child, _)| child.get_group_name())
+                        .expect("offload state has no child");
+                    labels.insert(
+                        *state,
+                        format!("{name}%[{beg}:{end}] ({child})"),
+                    );
+                }
+            }
+        }
+        labels
+    }
 }
 
 /// Represents a group of `Nodes` that execute in parallel.
@@ -1389,7 +1589,8 @@ pub struct ParNodes {
     /// Num Repeats
     pub num_repeats: u64,
     /// (Thread, interval thread is active).
-    /// Interval thread is active should always start at 0.
+    /// Threads may start after cycle 0, but at least one thread must be active
+    /// for the entire latency, since its FSM times the other threads.
     pub threads: Vec<(Node, (u64, u64))>,
 }
 
@@ -1404,7 +1605,8 @@ impl ParNodes {
             ir::Id,
             (OptionalStaticFSM, OptionalStaticFSM),
         >,
-        one_hot_cutoff: u64,
+        encoding_choice: EncodingChoice,
+        share_queries: bool,
     ) {
         for (thread, _) in &mut self.threads {
             thread.instantiate_fsms(
@@ -1412,19 +1614,28 @@ impl ParNodes {
                 coloring,
                 colors_to_max_values,
                 colors_to_fsm,
-                one_hot_cutoff,
+                encoding_choice,
+                share_queries,
             );
         }
     }
 
     /// Counts to N by recursively calling `count_to_n` on each thread.
+    /// Only threads that start at cycle 0 get `incr_start_cond`: the others
+    /// are started by the par group's go guards, which already wait on it.
     pub fn count_to_n(
         &mut self,
         builder: &mut ir::Builder,
         incr_start_cond: Option<ir::Guard<Nothing>>,
+        reset_guard: Option<ir::Guard<Nothing>>,
     ) {
-        for (thread, _) in &mut self.threads {
-            thread.count_to_n(builder, incr_start_cond.clone());
+        for (thread, (beg, _)) in &mut self.threads {
+            let cond = if *beg == 0 {
+                incr_start_cond.clone()
+            } else {
+                None
+            };
+            thread.count_to_n(builder, cond, reset_guard.clone());
         }
     }
 
@@ -1519,13 +1730,12 @@ impl ParNodes {
             Some(fsm_rc) => fsm_rc.borrow().get_unique_id(),
         };
 
-        let total_latency = self.latency * self.num_repeats;
         fsm_info_map.insert(
             early_reset_group.borrow().name(),
             (
                 fsm_identifier,
-                self.query_between((0, 1), builder),
-                self.query_between((total_latency - 1, total_latency), builder),
+                self.first_state_guard(builder),
+                self.final_state_guard(builder),
             ),
         );
 
@@ -1567,6 +1777,23 @@ impl ParNodes {
         let longest_node = self.get_longest_node();
         longest_node.query_between(query, builder)
     }
+
+    /// Returns a guard that is true during the first cycle of the par.
+    pub fn first_state_guard(
+        &mut self,
+        builder: &mut ir::Builder,
+    ) -> ir::Guard<Nothing> {
+        self.query_between((0, 1), builder)
+    }
+
+    /// Returns a guard that is true during the last cycle of the par.
+    pub fn final_state_guard(
+        &mut self,
+        builder: &mut ir::Builder,
+    ) -> ir::Guard<Nothing> {
+        let total_latency = self.latency * self.num_repeats;
+        self.query_between((total_latency - 1, total_latency), builder)
+    }
 }
 
 /// Used to add conflicts for graph coloring for sharing FSMs.
@@ -188,6 +188,8 @@ impl std::fmt::Debug for WireRewriter {
 /// w2.in = c;
 /// r.in = c;
 /// ```
+///
+/// Wires marked `@protected` are left untouched.
 pub struct CombProp {
     /// Disable automatic removal of some dead assignments needed for correctness and instead mark
     /// them with @dead.
@@ -281,7 +283,10 @@ impl CombProp {
             ir::PortParent::Cell(cell_wref) => {
                 let cr = cell_wref.upgrade();
                 let cell = cr.borrow();
+                // Protected wires (e.g., profiling probes) must keep their
+                // assignments, so we never propagate through them.
                 cell.is_primitive(Some("std_wire"))
+                    && !cell.attributes.has(ir::BoolAttr::Protected)
             }
             ir::PortParent::Group(_) => false,
             ir::PortParent::StaticGroup(_) => false,
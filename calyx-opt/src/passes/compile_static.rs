@@ -1,16 +1,18 @@
 use crate::analysis::{
-    GraphColoring, Node, ParNodes, SingleNode, StateType, StaticFSM,
+    EncodingChoice, FSMEncoding, GraphColoring, Node, ParNodes, SingleNode,
+    StateType, StaticFSM,
 };
 use crate::traversal::{
     Action, ConstructVisitor, Named, ParseVal, PassOpt, VisResult, Visitor,
 };
 use calyx_ir::{self as ir, Nothing, PortParent};
 use calyx_ir::{guard, structure, GetAttributes};
-use calyx_utils::{CalyxResult, Error};
+use calyx_utils::{CalyxResult, Error, OutputFile, WithPos};
 use core::panic;
 use ir::{build_assignments, RRC};
 use itertools::Itertools;
 use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::Write;
 use std::ops::Not;
 use std::rc::Rc;
 use std::vec;
@@ -35,15 +37,44 @@ pub struct CompileStatic {
     group_rewrites: ir::rewriter::PortRewriteMap,
 
     /// Command line arguments:
-    /// Cutoff for one hot encoding. Anything larger than the cutoff becomes
-    /// binary.
-    one_hot_cutoff: u64,
+    /// How to choose the encoding of each static FSM. Either a fixed encoding
+    /// or, in `auto` mode, a cutoff for one hot encoding (anything larger than
+    /// the cutoff becomes binary).
+    encoding_choice: EncodingChoice,
     /// Bool indicating whether to make the FSM pause (i.e., stop counting) when
     /// offloading. In order for compilation to make sense, this parameter must
     /// match the parameter for `static-inline`.
     offload_pause: bool,
     /// Bool indicating whether to greedily share the FSM registers
     greedy_share: bool,
+    /// Bool indicating whether binary FSMs build each query into a wire once
+    /// and share it, rather than rebuilding the comparison at every use.
+    share_queries: bool,
+    /// Bool indicating whether to keep the (compiled) static groups in the
+    /// component instead of removing them. Useful for debugging.
+    keep_static_groups: bool,
+    /// Bool indicating whether to add the optional conflicts between static
+    /// groups with differing state/repeat counts when sharing FSMs.
+    qor_conflicts: bool,
+    /// If set, static groups whose latencies differ by more than this amount
+    /// never share an FSM.
+    max_latency_diff: Option<u64>,
+    /// Bool indicating whether to log which static groups share an FSM.
+    print_coloring: bool,
+    /// Bool indicating whether to explicitly write the FSMs back to their
+    /// first state while the component's `@reset` port is high.
+    fsm_reset: bool,
+    /// If set, the FSM schedule of each static group is written here.
+    dump_schedule: Option<OutputFile>,
+    /// If set, the conflict graph used to share FSMs is written here in
+    /// Graphviz DOT form.
+    dump_conflict_graph: Option<OutputFile>,
+    /// Bool indicating whether every FSM (and every iteration counter) in a
+    /// component should be as wide as the widest one.
+    uniform_fsm_width: bool,
+    /// Bool indicating whether to add a protected wire per FSM that is high
+    /// while the FSM is running.
+    fsm_trace: bool,
 }
 
 impl Named for CompileStatic {
@@ -63,6 +94,17 @@ impl Named for CompileStatic {
             ParseVal::Num(0),
             PassOpt::parse_num,
         ),
+        PassOpt::new(
+            "fsm-encoding",
+            "The encoding to use for static FSMs: `binary`, `one-hot`, `gray`, or
+            `auto`. `auto` (the default) picks between binary and one-hot encoding
+            using `one-hot-cutoff`; the others ignore the cutoff. `gray` is never
+            picked automatically: its queries and incrementer compare the FSM
+            against every state they cover, so their logic grows linearly with
+            the number of states",
+            ParseVal::String("auto".to_string()),
+            PassOpt::parse_string,
+        ),
         PassOpt::new(
             "offload-pause",
             "Whether to pause the static FSM when offloading. Note that this
@@ -76,6 +118,85 @@ impl Named for CompileStatic {
             "Whether to greedily share the FSMs",
             ParseVal::Bool(true),
             PassOpt::parse_bool,
+        ),
+        PassOpt::new(
+            "share-queries",
+            "Whether binary FSMs should compute each state query once in a wire
+            and share it across all of its uses",
+            ParseVal::Bool(false),
+            PassOpt::parse_bool,
+        ),
+        PassOpt::new(
+            "keep-static-groups",
+            "Whether to keep the static groups in the component after compiling
+            them (for debugging). Defaults to false",
+            ParseVal::Bool(false),
+            PassOpt::parse_bool,
+        ),
+        PassOpt::new(
+            "qor-conflicts",
+            "Whether to add conflicts between static groups whose FSMs have
+            different numbers of states or repeats, which may improve the quality
+            of FSM sharing. Defaults to false",
+            ParseVal::Bool(false),
+            PassOpt::parse_bool,
+        ),
+        PassOpt::new(
+            "max-latency-diff",
+            "The maximum difference in latency between two static groups that
+            share an FSM. Negative values (the default) mean there is no limit",
+            ParseVal::Num(-1),
+            PassOpt::parse_num,
+        ),
+        PassOpt::new(
+            "print-coloring",
+            "Whether to log (at the info level) which static groups share an FSM,
+            along with the encoding and bitwidth of each shared FSM",
+            ParseVal::Bool(false),
+            PassOpt::parse_bool,
+        ),
+        PassOpt::new(
+            "fsm-reset",
+            "Whether to add assignments that write every static FSM back to its
+            first state while the component's reset port is high, instead of
+            relying on how the backend resets registers. Defaults to false",
+            ParseVal::Bool(false),
+            PassOpt::parse_bool,
+        ),
+        PassOpt::new(
+            "dump-schedule",
+            "Write the FSM schedule of each static group to this file, i.e.,
+            which cycle or offloaded child each FSM state corresponds to",
+            ParseVal::OutStream(OutputFile::Null),
+            PassOpt::parse_outstream,
+        ),
+        PassOpt::new(
+            "dump-conflict-graph",
+            "Write the conflict graph between static groups that is colored to
+            share FSMs to this file, in Graphviz DOT form. Nothing is written when
+            greedy-share is off",
+            ParseVal::OutStream(OutputFile::Null),
+            PassOpt::parse_outstream,
+        ),
+        PassOpt::new(
+            "uniform-fsm-width",
+            "Whether to size every static FSM in a component for the largest
+            number of states of any FSM in that component, and every iteration
+            counter for the largest number of repeats, so that all of these
+            registers have the same width. This costs area (wider registers,
+            incrementers, and comparators) for the smaller FSMs. Defaults to
+            false",
+            ParseVal::Bool(false),
+            PassOpt::parse_bool,
+        ),
+        PassOpt::new(
+            "fsm-trace",
+            "Whether to add a protected 1-bit wire `<fsm>_running` for each
+            static FSM, which is high while the FSM is past its first state.
+            FSMs rest in their first state when idle, so the first cycle of each
+            run is not traced. Defaults to false",
+            ParseVal::Bool(false),
+            PassOpt::parse_bool,
         )
 
         ]
@@ -86,10 +207,35 @@ impl ConstructVisitor for CompileStatic {
     fn from(ctx: &ir::Context) -> CalyxResult<Self> {
         let opts = Self::get_opts(ctx);
 
+        let encoding_choice = match opts["fsm-encoding"].string().as_str() {
+            "auto" => EncodingChoice::Auto {
+                one_hot_cutoff: opts["one-hot-cutoff"].pos_num().unwrap(),
+            },
+            "binary" => EncodingChoice::Fixed(FSMEncoding::Binary),
+            "one-hot" => EncodingChoice::Fixed(FSMEncoding::OneHot),
+            "gray" => EncodingChoice::Fixed(FSMEncoding::Gray),
+            enc => {
+                return Err(Error::misc(format!(
+                    "Unknown fsm-encoding `{enc}`. Valid values are `binary`, `one-hot`, `gray`, or `auto`"
+                )))
+            }
+        };
+
         Ok(CompileStatic {
-            one_hot_cutoff: opts["one-hot-cutoff"].pos_num().unwrap(),
+            encoding_choice,
             offload_pause: opts["offload-pause"].bool(),
             greedy_share: opts["greedy-share"].bool(),
+            share_queries: opts["share-queries"].bool(),
+            keep_static_groups: opts["keep-static-groups"].bool(),
+            qor_conflicts: opts["qor-conflicts"].bool(),
+            max_latency_diff: opts["max-latency-diff"].pos_num(),
+            print_coloring: opts["print-coloring"].bool(),
+            fsm_reset: opts["fsm-reset"].bool(),
+            dump_schedule: opts["dump-schedule"].not_null_outstream(),
+            dump_conflict_graph: opts["dump-conflict-graph"]
+                .not_null_outstream(),
+            uniform_fsm_width: opts["uniform-fsm-width"].bool(),
+            fsm_trace: opts["fsm-trace"].bool(),
             reset_early_map: HashMap::new(),
             wrapper_map: HashMap::new(),
             signal_reg_map: HashMap::new(),
@@ -114,7 +260,8 @@ impl CompileStatic {
     /// `done` signal should be high the next cycle after that.
     /// `add_resetting_logic` is a bool; since the same FSM/signal_reg pairing
     /// may be used for multiple static islands, and we only add resetting logic
-    /// for the signal_reg once.
+    /// for the signal_reg once. Resetting assignments that already exist in
+    /// the component are not added again.
     fn build_wrapper_group(
         fsm_final_state: ir::Guard<Nothing>,
         group_name: &ir::Id,
@@ -170,7 +317,19 @@ impl CompileStatic {
                 signal_reg["write_en"] = signal_reg_guard ? signal_on["out"];
                 signal_reg["in"] =  signal_reg_guard ? signal_off["out"];
             );
-            builder.add_continuous_assignments(continuous_assigns.to_vec());
+            // Only add the assignments that are not already there, so that
+            // compiling a component more than once does not duplicate them.
+            let new_assigns = continuous_assigns
+                .into_iter()
+                .filter(|asgn| {
+                    !builder.component.continuous_assignments.iter().any(|a| {
+                        *a.dst.borrow() == *asgn.dst.borrow()
+                            && *a.src.borrow() == *asgn.src.borrow()
+                            && a.guard == asgn.guard
+                    })
+                })
+                .collect_vec();
+            builder.add_continuous_assignments(new_assigns);
         }
         g.borrow_mut().assignments = group_assigns.to_vec();
         g.borrow_mut().attributes =
@@ -253,6 +412,30 @@ impl CompileStatic {
 // These are the functions used to allocate FSMs to static islands through a
 // greedy coloring algorithm.
 impl CompileStatic {
+    // If `self.fsm_reset` is set, returns a guard on the component's `@reset`
+    // port, which `count_to_n` uses to force the FSMs back to their first
+    // state. Otherwise, returns `None`.
+    fn fsm_reset_guard(
+        &self,
+        builder: &ir::Builder,
+    ) -> CalyxResult<Option<ir::Guard<Nothing>>> {
+        if !self.fsm_reset {
+            return Ok(None);
+        }
+        let reset = builder
+            .component
+            .signature
+            .borrow()
+            .find_unique_with_attr(ir::BoolAttr::Reset)?
+            .ok_or_else(|| {
+                Error::malformed_structure(format!(
+                    "component `{}` has no @reset port for fsm-reset",
+                    builder.component.name
+                ))
+            })?;
+        Ok(Some(ir::Guard::port(reset)))
+    }
+
     // Given a list of `static_groups`, find the group named `name`.
     // If there is no such group, then there is an unreachable! error.
     fn find_static_group(
@@ -304,42 +487,41 @@ impl CompileStatic {
                 );
             }
             ir::Control::Par(par) => {
-                // `sgroup_conflict_vec` is a vec of HashSets.
-                // Each item in the vec corresponds to a par thread, and holds
-                // all of the groups executed in that thread.
-                let mut sgroup_conflict_vec: Vec<HashSet<ir::Id>> = Vec::new();
-                for stmt in &par.stmts {
-                    sgroup_conflict_vec.push(HashSet::from_iter(
-                        Self::get_static_enables(stmt),
-                    ));
-                }
-                for (thread1_st_enables, thread2_st_enables) in
-                    sgroup_conflict_vec.iter().tuple_combinations()
-                {
-                    // For each static group g1 enabled in thread1 and static
-                    // group g2 enabled in thread2 respectively, add a conflict
-                    // each node in g1 and g2's corresponding trees.
-                    for static_enable1 in thread1_st_enables {
-                        for static_enable2 in thread2_st_enables {
-                            // Getting tree1
-                            let tree1 = fsm_trees
-                                .iter()
-                                .find(|tree| {
-                                    tree.get_group_name() == static_enable1
-                                })
-                                .expect("couldn't find FSM tree");
-                            // Getting tree2
-                            let tree2 = fsm_trees
+                // Threads of an `@exclusive_threads` par never overlap, so
+                // their groups don't conflict with each other.
+                if !par.attributes.has(ir::BoolAttr::ExclusiveThreads) {
+                    // `thread_trees` holds, for each par thread, the trees of
+                    // the static groups enabled in that thread. Groups without
+                    // a tree are skipped, so threads that only run dynamic
+                    // groups simply add no conflicts.
+                    let thread_trees = par
+                        .stmts
+                        .iter()
+                        .map(|stmt| {
+                            let enables: HashSet<ir::Id> = HashSet::from_iter(
+                                Self::get_static_enables(stmt),
+                            );
+                            fsm_trees
                                 .iter()
-                                .find(|tree| {
-                                    tree.get_group_name() == static_enable2
+                                .filter(|tree| {
+                                    enables.contains(&tree.get_group_name())
                                 })
-                                .expect("couldn't find tree");
-                            // Add conflict between each node in tree1 and tree2
-                            for sgroup1 in tree1.get_all_nodes() {
-                                for sgroup2 in tree2.get_all_nodes() {
-                                    conflict_graph
-                                        .insert_conflict(&sgroup1, &sgroup2)
+                                .collect_vec()
+                        })
+                        .collect_vec();
+                    for (thread1_trees, thread2_trees) in
+                        thread_trees.iter().tuple_combinations()
+                    {
+                        // For each tree of a static group enabled in thread1
+                        // and tree of a static group enabled in thread2, add a
+                        // conflict between each of their nodes.
+                        for tree1 in thread1_trees {
+                            for tree2 in thread2_trees {
+                                for sgroup1 in tree1.get_all_nodes() {
+                                    for sgroup2 in tree2.get_all_nodes() {
+                                        conflict_graph
+                                            .insert_conflict(&sgroup1, &sgroup2)
+                                    }
                                 }
                             }
                         }
@@ -383,23 +565,70 @@ impl CompileStatic {
         cur_max
     }
 
+    /// Adds conflicts between static groups that are unlikely to share an
+    /// FSM well: groups where exactly one of them needs no FSM (i.e., has a
+    /// single state), or groups with a different number of repeats.
+    /// These conflicts are not necessary for correctness.
+    fn add_qor_conflicts(
+        tree_objects: &Vec<Node>,
+        sgroups: &[ir::RRC<ir::StaticGroup>],
+        conflict_graph: &mut GraphColoring<ir::Id>,
+    ) {
+        for (sgroup1, sgroup2) in sgroups.iter().tuple_combinations() {
+            let (name1, name2) =
+                (sgroup1.borrow().name(), sgroup2.borrow().name());
+            let max_num_states1 = Self::get_max_num_states(name1, tree_objects);
+            let max_num_repeats1 =
+                Self::get_max_num_repeats(name1, tree_objects);
+            let max_num_states2 = Self::get_max_num_states(name2, tree_objects);
+            let max_num_repeats2 =
+                Self::get_max_num_repeats(name2, tree_objects);
+            if ((max_num_states1 == 1) != (max_num_states2 == 1))
+                || (max_num_repeats1 != max_num_repeats2)
+            {
+                conflict_graph.insert_conflict(&name1, &name2);
+            }
+        }
+    }
+
+    /// Adds conflicts between static groups whose latencies differ by more
+    /// than `max_diff`, so that a short group doesn't get stuck with a
+    /// (wide) FSM sized for a much longer group.
+    fn add_latency_diff_conflicts(
+        sgroups: &[ir::RRC<ir::StaticGroup>],
+        max_diff: u64,
+        conflict_graph: &mut GraphColoring<ir::Id>,
+    ) {
+        for (sgroup1, sgroup2) in sgroups.iter().tuple_combinations() {
+            let (sgroup1, sgroup2) = (sgroup1.borrow(), sgroup2.borrow());
+            if sgroup1.get_latency().abs_diff(sgroup2.get_latency()) > max_diff
+            {
+                conflict_graph
+                    .insert_conflict(&sgroup1.name(), &sgroup2.name());
+            }
+        }
+    }
+
     /// Creates a graph (one node per item in `sgroup` where nodes are the `sgroup`'s
     /// names).
     /// Use `tree_objects` and `control` to draw conflicts between any two nodes
     /// that could be executing in parallel, and returns a greedy coloring of the
     /// graph.
+    /// Groups marked `@fsm_distinct` conflict with every other group, and
+    /// groups with the same `@fsm_group` always get the same color.
     pub fn get_coloring(
-        &self,
+        &mut self,
+        comp_name: ir::Id,
         tree_objects: &Vec<Node>,
         sgroups: &[ir::RRC<ir::StaticGroup>],
         control: &mut ir::Control,
-    ) -> HashMap<ir::Id, ir::Id> {
+    ) -> CalyxResult<HashMap<ir::Id, ir::Id>> {
         if !self.greedy_share {
             // If !greedy_share just give each sgroup its own color.
-            return sgroups
+            return Ok(sgroups
                 .iter()
                 .map(|g| (g.borrow().name(), g.borrow().name()))
-                .collect();
+                .collect());
         }
         let mut conflict_graph: GraphColoring<ir::Id> =
             GraphColoring::from(sgroups.iter().map(|g| g.borrow().name()));
@@ -415,31 +644,249 @@ impl CompileStatic {
             // same tree.
             tree.add_conflicts(&mut conflict_graph);
         }
+        // Conflicts requested by the user
+        for sgroup in sgroups {
+            let sgroup = sgroup.borrow();
+            if sgroup.attributes.has(ir::BoolAttr::FSMDistinct) {
+                for other in sgroups {
+                    let other_name = other.borrow().name();
+                    if other_name != sgroup.name() {
+                        conflict_graph
+                            .insert_conflict(&sgroup.name(), &other_name);
+                    }
+                }
+            }
+        }
+        // Groups in the same `@fsm_group` must be able to share an FSM.
+        let mut representatives = Self::get_fsm_group_representatives(sgroups);
+        let rep = |name: &ir::Id| *representatives.get(name).unwrap_or(name);
+        for (sgroup1, sgroup2) in sgroups.iter().tuple_combinations() {
+            let (name1, name2) =
+                (sgroup1.borrow().name(), sgroup2.borrow().name());
+            if representatives.contains_key(&name1)
+                && rep(&name1) == rep(&name2)
+                && conflict_graph.has_conflict(&name1, &name2)
+            {
+                return Err(Error::malformed_structure(format!(
+                    "static groups `{name1}` and `{name2}` have the same fsm_group but cannot share an FSM"
+                ))
+                .with_pos(&sgroup1.borrow().attributes));
+            }
+        }
+
         // Optional conflicts to ?potentially? improve QoR
-        // for (sgroup1, sgroup2) in sgroups.iter().tuple_combinations() {
-        //     let max_num_states1 =
-        //         Self::get_max_num_states(sgroup1.borrow().name(), tree_objects);
-        //     let max_num_repeats1 = Self::get_max_num_repeats(
-        //         sgroup1.borrow().name(),
-        //         tree_objects,
-        //     );
-        //     let max_num_states2 =
-        //         Self::get_max_num_states(sgroup2.borrow().name(), tree_objects);
-        //     let max_num_repeats2 = Self::get_max_num_repeats(
-        //         sgroup2.borrow().name(),
-        //         tree_objects,
-        //     );
-        //     if ((max_num_states1 == 1) != (max_num_states2 == 1))
-        //         || ((max_num_repeats1) != (max_num_repeats2))
-        //     {
-        //         conflict_graph.insert_conflict(
-        //             &sgroup1.borrow().name(),
-        //             &sgroup2.borrow().name(),
-        //         );
-        //     }
-        // }
+        if self.qor_conflicts {
+            Self::add_qor_conflicts(tree_objects, sgroups, &mut conflict_graph);
+        }
+        if let Some(max_diff) = self.max_latency_diff {
+            Self::add_latency_diff_conflicts(
+                sgroups,
+                max_diff,
+                &mut conflict_graph,
+            );
+        }
+
+        if let Some(out) = &mut self.dump_conflict_graph {
+            write!(out.get_write(), "// {comp_name}\n{conflict_graph}\n")
+                .map_err(|err| {
+                    Error::write_error(format!(
+                        "failed to write the conflict graph: {err}"
+                    ))
+                })?;
+        }
+
+        // The branches of a `static if` must share an FSM, since the `if`
+        // group only queries the FSM of its first branch.
+        for branches in tree_objects.iter().flat_map(Self::get_if_branches) {
+            let new_rep =
+                *representatives.get(&branches[0]).unwrap_or(&branches[0]);
+            let old_reps: HashSet<ir::Id> = branches
+                .iter()
+                .map(|name| *representatives.get(name).unwrap_or(name))
+                .collect();
+            for sgroup in sgroups {
+                let name = sgroup.borrow().name();
+                if old_reps
+                    .contains(representatives.get(&name).unwrap_or(&name))
+                {
+                    representatives.insert(name, new_rep);
+                }
+            }
+        }
+        let rep = |name: &ir::Id| *representatives.get(name).unwrap_or(name);
+        for (sgroup1, sgroup2) in sgroups.iter().tuple_combinations() {
+            let (name1, name2) =
+                (sgroup1.borrow().name(), sgroup2.borrow().name());
+            if representatives.contains_key(&name1)
+                && rep(&name1) == rep(&name2)
+                && conflict_graph.has_conflict(&name1, &name2)
+            {
+                return Err(Error::malformed_structure(format!(
+                    "static groups `{name1}` and `{name2}` are branches of a static if but cannot share an FSM. Run {} before {} to inline the if instead",
+                    crate::passes::StaticInliner::name(),
+                    Self::name()
+                ))
+                .with_pos(&sgroup1.borrow().attributes));
+            }
+        }
+
+        if representatives.is_empty() {
+            return Ok(conflict_graph.color_greedy(None, true));
+        }
+        // Force the groups in each `@fsm_group` to share by coloring the graph
+        // where each of them is replaced with the group's representative.
+        let coloring = conflict_graph.merge_nodes(rep).color_greedy(None, true);
+        Ok(sgroups
+            .iter()
+            .map(|g| {
+                let name = g.borrow().name();
+                (name, coloring[&rep(&name)])
+            })
+            .collect())
+    }
+
+    /// Maps each static group with an `@fsm_group` attribute to the first
+    /// group in `sgroups` with the same `@fsm_group` value.
+    fn get_fsm_group_representatives(
+        sgroups: &[ir::RRC<ir::StaticGroup>],
+    ) -> HashMap<ir::Id, ir::Id> {
+        let mut value_to_rep: HashMap<u64, ir::Id> = HashMap::new();
+        let mut representatives = HashMap::new();
+        for sgroup in sgroups {
+            let sgroup = sgroup.borrow();
+            if let Some(value) = sgroup.attributes.get(ir::NumAttr::FSMGroup) {
+                let rep = *value_to_rep.entry(value).or_insert(sgroup.name());
+                representatives.insert(sgroup.name(), rep);
+            }
+        }
+        representatives
+    }
 
-        conflict_graph.color_greedy(None, true)
+    /// Returns the names of the branches of each `static if` in `tree`.
+    fn get_if_branches(tree: &Node) -> Vec<Vec<ir::Id>> {
+        match tree {
+            Node::Single(single) => {
+                let mut branches: Vec<Vec<ir::Id>> = single
+                    .children
+                    .iter()
+                    .flat_map(|(child, _)| Self::get_if_branches(child))
+                    .collect();
+                if single.branch_children {
+                    branches.push(
+                        single
+                            .children
+                            .iter()
+                            .map(|(child, _)| child.get_group_name())
+                            .collect(),
+                    );
+                }
+                branches
+            }
+            Node::Par(par) => par
+                .threads
+                .iter()
+                .flat_map(|(thread, _)| Self::get_if_branches(thread))
+                .collect(),
+        }
+    }
+
+    /// Logs the groups assigned to each color, along with the encoding and
+    /// bitwidth of the registers that will be instantiated for that color.
+    fn log_coloring(
+        &self,
+        comp_name: ir::Id,
+        coloring: &HashMap<ir::Id, ir::Id>,
+        colors_to_max_values: &HashMap<ir::Id, (u64, u64)>,
+    ) {
+        let mut colors_to_sgroups: HashMap<ir::Id, Vec<ir::Id>> =
+            HashMap::new();
+        for (group_name, color) in coloring {
+            colors_to_sgroups
+                .entry(*color)
+                .or_default()
+                .push(*group_name);
+        }
+        for (color, sgroups) in colors_to_sgroups
+            .into_iter()
+            .sorted_by_key(|(color, _)| color.to_string())
+        {
+            let (num_states, num_repeats) = colors_to_max_values[&color];
+            // Describes a register counting to `n`, or the lack of one.
+            let describe = |n: u64| {
+                if n == 1 {
+                    "none".to_string()
+                } else {
+                    let encoding = self.encoding_choice.choose_encoding(n);
+                    format!(
+                        "{:?}, {} bits",
+                        encoding,
+                        StaticFSM::required_bitwidth(n, encoding)
+                    )
+                }
+            };
+            log::info!(
+                "{comp_name}: color {color}: groups [{}]; fsm: {} ({num_states} states); repeat counter: {} ({num_repeats} repeats)",
+                sgroups.iter().map(ir::Id::to_string).sorted().join(", "),
+                describe(num_states),
+                describe(num_repeats),
+            );
+        }
+    }
+
+    /// Writes the FSM schedule of every node in `tree` to `out`: each state
+    /// of a node's FSM is listed with the cycle it counts or the child it
+    /// offloads to (see [SingleNode::state_labels]).
+    fn write_schedule(
+        out: &mut OutputFile,
+        comp_name: ir::Id,
+        tree: &Node,
+    ) -> CalyxResult<()> {
+        match tree {
+            Node::Single(single_node) => {
+                writeln!(
+                    out.get_write(),
+                    "// {comp_name}: `{}` (latency {}, {} repeats, {} states)",
+                    single_node.root.0,
+                    single_node.latency,
+                    single_node.num_repeats,
+                    single_node.num_states,
+                )?;
+                for (state, label) in
+                    single_node.state_labels().into_iter().sorted()
+                {
+                    writeln!(out.get_write(), "//   {state}: {label}")?;
+                }
+                // A child that is triggered over several intervals is only
+                // written once.
+                let mut written = HashSet::new();
+                for (child, _) in &single_node.children {
+                    if written.insert(child.get_group_name()) {
+                        Self::write_schedule(out, comp_name, child)?;
+                    }
+                }
+            }
+            Node::Par(par_nodes) => {
+                writeln!(
+                    out.get_write(),
+                    "// {comp_name}: `{}` (latency {}, {} repeats) runs {}",
+                    par_nodes.group_name,
+                    par_nodes.latency,
+                    par_nodes.num_repeats,
+                    par_nodes
+                        .threads
+                        .iter()
+                        .map(|(thread, (beg, end))| format!(
+                            "`{}` for %[{beg}:{end}]",
+                            thread.get_group_name()
+                        ))
+                        .join(", "),
+                )?;
+                for (thread, _) in &par_nodes.threads {
+                    Self::write_schedule(out, comp_name, thread)?;
+                }
+            }
+        }
+        Ok(())
     }
 
     /// Given a coloring of group names, returns a Hashmap that maps:
@@ -479,39 +926,125 @@ impl CompileStatic {
             })
             .collect()
     }
+
+    /// Adds a protected wire `<fsm>_running` for each FSM in `colors_to_fsms`
+    /// that is continuously driven high while the FSM is past its first state.
+    /// Repeat counters are not traced.
+    fn add_fsm_trace_wires(
+        colors_to_fsms: &HashMap<
+            ir::Id,
+            (OptionalStaticFSM, OptionalStaticFSM),
+        >,
+        builder: &mut ir::Builder,
+    ) {
+        let fsms = colors_to_fsms
+            .values()
+            .filter_map(|(fsm, _)| fsm.as_ref())
+            .sorted_by_key(|fsm| fsm.borrow().get_unique_id().to_string());
+        for fsm in fsms {
+            let mut fsm = fsm.borrow_mut();
+            let num_states = fsm.get_num_states();
+            let running = fsm.query_between(builder, (1, num_states));
+            let wire = builder.add_primitive(
+                format!("{}_running", fsm.get_unique_id()),
+                "std_wire",
+                &[1],
+            );
+            wire.borrow_mut().add_attribute(ir::BoolAttr::Protected, 1);
+            let signal_on = builder.add_constant(1, 1);
+            let assign = builder.build_assignment(
+                wire.borrow().get("in"),
+                signal_on.borrow().get("out"),
+                *running,
+            );
+            builder.add_continuous_assignments(vec![assign]);
+        }
+    }
+
+    /// Raises the number of states of every color that needs an FSM to the
+    /// largest number of states of any color, so all FSMs get the same width.
+    /// The number of repeats is padded the same way, so that all iteration
+    /// counters get the same width as well. Colors with a single state (or a
+    /// single repeat) have no such register and are left alone.
+    fn pad_color_max_values(
+        colors_to_max_values: &mut HashMap<ir::Id, (u64, u64)>,
+    ) {
+        let max_num_states = colors_to_max_values
+            .values()
+            .map(|(num_states, _)| *num_states)
+            .max()
+            .unwrap_or(1);
+        let max_num_repeats = colors_to_max_values
+            .values()
+            .map(|(_, num_repeats)| *num_repeats)
+            .max()
+            .unwrap_or(1);
+        for (num_states, num_repeats) in colors_to_max_values.values_mut() {
+            if *num_states != 1 {
+                *num_states = max_num_states;
+            }
+            if *num_repeats != 1 {
+                *num_repeats = max_num_repeats;
+            }
+        }
+    }
 }
 
 impl CompileStatic {
-    /// `get_interval_from_guard` returns the interval found within guard `g`.
+    /// `get_interval_from_guard` returns the intervals found within guard `g`,
+    /// sorted and disjoint.
     /// The tricky part is that sometimes there can be an implicit latency
     /// `lat` that is not explicitly stated (i.e., every assignment in a
     /// group with latency n has an implicit guard of %[0:n]). `lat` is `n`.
+    /// An `or` of intervals (e.g., `%[0:2] | %[4:6]`) gives multiple
+    /// intervals, and an `and` of intervals gives their intersection.
     fn get_interval_from_guard(
         g: &ir::Guard<ir::StaticTiming>,
         lat: u64,
-    ) -> (u64, u64) {
+    ) -> Vec<(u64, u64)> {
         match g {
             calyx_ir::Guard::Info(static_timing_interval) => {
-                static_timing_interval.get_interval()
+                vec![static_timing_interval.get_interval()]
             }
             calyx_ir::Guard::Not(_)
             | calyx_ir::Guard::CompOp(_, _, _)
             | calyx_ir::Guard::Port(_)
-            | calyx_ir::Guard::True => (0, lat),
+            | calyx_ir::Guard::True => vec![(0, lat)],
             calyx_ir::Guard::And(l, r) => {
-                let ((beg1, end1), (beg2, end2)) = (
+                let (l_intervals, r_intervals) = (
                     Self::get_interval_from_guard(l, lat),
                     Self::get_interval_from_guard(r, lat),
                 );
-                assert!(end1 - beg1 == lat || end2 - beg2 == lat);
-                if end1 - beg1 == lat {
-                    (beg2, end2)
-                } else {
-                    (beg1, end1)
+                // Both sides are sorted and disjoint, so their pairwise
+                // intersections are too.
+                l_intervals
+                    .iter()
+                    .cartesian_product(r_intervals.iter())
+                    .filter_map(|((beg1, end1), (beg2, end2))| {
+                        let (beg, end) = (
+                            *std::cmp::max(beg1, beg2),
+                            *std::cmp::min(end1, end2),
+                        );
+                        (beg < end).then_some((beg, end))
+                    })
+                    .collect()
+            }
+            calyx_ir::Guard::Or(l, r) => {
+                let mut intervals = Self::get_interval_from_guard(l, lat);
+                intervals.extend(Self::get_interval_from_guard(r, lat));
+                intervals.sort();
+                // Merge the intervals that overlap or touch.
+                let mut merged: Vec<(u64, u64)> = vec![];
+                for (beg, end) in intervals {
+                    match merged.last_mut() {
+                        Some((_, last_end)) if beg <= *last_end => {
+                            *last_end = std::cmp::max(*last_end, end)
+                        }
+                        _ => merged.push((beg, end)),
+                    }
                 }
-
+                merged
             }
-            ir::Guard::Or(_, _) => unreachable!("Shouldn't try to get interval from guard if there is an 'or' in the guard"),
         }
     }
 
@@ -574,11 +1107,14 @@ impl CompileStatic {
     /// Given a static group `target_name` and vec of `static_groups`, builds a
     /// `tree_object` for group `target_name` that repeats itself `num_repeat`
     /// times.
+    /// Since the tree's FSM pauses while offloading to a child, returns an
+    /// error if the latency of the resulting schedule doesn't match the
+    /// group's declared latency.
     fn build_tree_object(
         target_name: ir::Id,
         static_groups: &[ir::RRC<ir::StaticGroup>],
         num_repeats: u64,
-    ) -> Node {
+    ) -> CalyxResult<Node> {
         // Find the group that will serve as the root of the tree.
         let target_group = static_groups
             .iter()
@@ -598,53 +1134,132 @@ impl CompileStatic {
                         panic!("")
                     }
                 }
-                PortParent::Group(_) => panic!(""),
+                PortParent::Group(_) => unreachable!(
+                    "static groups writing to dynamic groups should have been caught by `check_static_go_writes`"
+                ),
                 PortParent::StaticGroup(sgroup) => {
                     assert!(assign.src.borrow().is_constant(1, 1));
-                    let (beg, end) = Self::get_interval_from_guard(
-                        &assign.guard,
-                        target_group.borrow().get_latency(),
-                    );
                     let name: calyx_ir::Id = sgroup.upgrade().borrow().name();
-                    // Need the following lines to determine `num_repeats`
+                    // Need the child's latency to determine `num_repeats`
                     // for the child.
                     let target_child_latency =
                         Self::get_sgroup_latency(name, static_groups);
-                    let child_execution_time = end - beg;
-                    assert!(
-                        child_execution_time % target_child_latency == 0,
-                        "child will execute only part of an iteration"
+                    // The child gets its own offload state for each disjoint
+                    // interval it is triggered in. All of these states share
+                    // the child's registers, so the child has to run for the
+                    // same number of iterations each time.
+                    let intervals = Self::get_interval_from_guard(
+                        &assign.guard,
+                        target_group.borrow().get_latency(),
                     );
-                    let child_num_repeats =
-                        child_execution_time / target_child_latency;
-                    // Recursively build a tree for the child.
-                    children_vec.push((
-                        Self::build_tree_object(
-                            name,
-                            static_groups,
-                            child_num_repeats,
-                        ),
-                        (beg, end),
-                    ));
+                    if !intervals.iter().map(|(beg, end)| end - beg).all_equal()
+                    {
+                        return Err(Error::malformed_structure(format!(
+                            "static group `{target_name}` triggers `{name}` over intervals of different lengths: {}",
+                            intervals
+                                .iter()
+                                .map(|(beg, end)| format!("%[{beg}:{end}]"))
+                                .join(", ")
+                        ))
+                        .with_pos(&assign.attributes));
+                    }
+                    for (beg, end) in intervals {
+                        let child_execution_time = end - beg;
+                        // The FSM waits for the child to finish its final
+                        // iteration, so a child that is only enabled for part
+                        // of an iteration still runs the whole thing. We check
+                        // whether this changes the group's latency below.
+                        let child_num_repeats =
+                            child_execution_time.div_ceil(target_child_latency);
+                        // Recursively build a tree for the child.
+                        children_vec.push((
+                            Self::build_tree_object(
+                                name,
+                                static_groups,
+                                child_num_repeats,
+                            )?,
+                            (beg, end),
+                        ));
+                    }
                 }
             }
         }
 
         if target_group_ref.attributes.has(ir::BoolAttr::ParCtrl) {
             // If we are in a par group, then the "children" are actually
-            // threads that should all start at 0.
-            assert!(children_vec.iter().all(|(_, (beg, _))| *beg == 0));
-            Node::Par(ParNodes {
+            // threads. Threads may start at any cycle, but the go guards of
+            // the par group are realized using the FSM of its longest thread,
+            // so that thread has to run for the group's entire latency.
+            let latency = target_group_ref.latency;
+            if !children_vec
+                .iter()
+                .any(|(_, (beg, end))| *beg == 0 && *end == latency)
+            {
+                return Err(Error::malformed_structure(format!(
+                    "static par group `{target_name}` has latency {latency} but none of its threads execute for %[0:{latency}]"
+                ))
+                .with_pos(&target_group_ref.attributes));
+            }
+            // Threads don't pause the par group, so each of them has to run
+            // whole iterations of its group.
+            if let Some((thread, (beg, end))) =
+                children_vec.iter().find(|(thread, (beg, end))| {
+                    thread.get_latency() * thread.get_num_repeats() != end - beg
+                })
+            {
+                return Err(Error::malformed_structure(format!(
+                    "static par group `{target_name}` enables thread `{}` for %[{beg}:{end}], which is not a whole number of its iterations",
+                    thread.get_group_name()
+                ))
+                .with_pos(&target_group_ref.attributes));
+            }
+            Ok(Node::Par(ParNodes {
                 group_name: target_name,
                 threads: children_vec,
                 latency: target_group_ref.latency,
                 num_repeats,
-            })
+            }))
+        } else if target_group_ref.attributes.has(ir::BoolAttr::IfCtrl) {
+            // If we are in an if group, then the children are its branches,
+            // which both run for the group's entire latency (see
+            // `lower_static_ifs`). The group itself offloads to whichever
+            // branch runs, so it has a single state.
+            let latency = target_group_ref.latency;
+            let (fsm_schedule, num_states) =
+                Self::build_tree_schedule(&[(0, latency)], latency);
+            Ok(Node::Single(SingleNode {
+                latency,
+                fsm_cell: None,
+                iter_count_cell: None,
+                root: (target_name, vec![]),
+                fsm_schedule,
+                children: children_vec,
+                branch_children: true,
+                num_repeats,
+                num_states,
+            }))
         } else {
             // If we are in a regular group, then the children should be
             // non-overlapping.
             children_vec.sort_by_key(|(_, interval)| *interval);
-            assert!(Self::are_ranges_non_overlapping(&children_vec));
+            if let Some(((beg1, end1), (beg2, end2))) =
+                Self::find_overlapping_ranges(&children_vec)
+            {
+                return Err(Error::malformed_structure(format!(
+                    "static group `{target_name}` enables children during overlapping intervals %[{beg1}:{end1}] and %[{beg2}:{end2}]"
+                ))
+                .with_pos(&target_group_ref.attributes));
+            }
+            if let Some((_, (_, end))) = children_vec
+                .iter()
+                .find(|(_, (_, end))| *end > target_group_ref.latency)
+            {
+                return Err(Error::malformed_structure(format!(
+                    "static group `{target_name}` has latency {} but enables a child until cycle {end}",
+                    target_group_ref.latency
+                ))
+                .with_pos(&target_group_ref.attributes));
+            }
             let (fsm_schedule, num_states) = Self::build_tree_schedule(
                 &children_vec
                     .iter()
@@ -652,16 +1267,38 @@ impl CompileStatic {
                     .collect_vec(),
                 target_group_ref.latency,
             );
-            Node::Single(SingleNode {
+            // The FSM pauses while offloading, so the latency it actually
+            // realizes is the time spent outside of the offload intervals plus
+            // the time each child takes to run all of its iterations.
+            let offload_latency = children_vec
+                .iter()
+                .map(|(_, (beg, end))| end - beg)
+                .sum::<u64>();
+            let paused_latency = target_group_ref.latency - offload_latency
+                + children_vec
+                    .iter()
+                    .map(|(child, _)| {
+                        child.get_latency() * child.get_num_repeats()
+                    })
+                    .sum::<u64>();
+            if paused_latency != target_group_ref.latency {
+                return Err(Error::malformed_structure(format!(
+                    "static group `{target_name}` has latency {} but pausing its FSM while offloading gives it a latency of {paused_latency}",
+                    target_group_ref.latency
+                ))
+                .with_pos(&target_group_ref.attributes));
+            }
+            Ok(Node::Single(SingleNode {
                 latency: target_group_ref.latency,
                 fsm_cell: None,
                 iter_count_cell: None,
                 root: (target_name, vec![]),
                 fsm_schedule,
                 children: children_vec,
+                branch_children: false,
                 num_repeats,
                 num_states,
-            })
+            }))
         }
     }
 
@@ -691,19 +1328,21 @@ impl CompileStatic {
             // Looking for static_child[go] = %[i:j] ? 1'd1; to build children.
             match &assign.dst.borrow().parent {
                 PortParent::Cell(_) => (),
-                PortParent::Group(_) => unreachable!(""),
+                PortParent::Group(_) => unreachable!(
+                    "static groups writing to dynamic groups should have been caught by `check_static_go_writes`"
+                ),
                 PortParent::StaticGroup(sgroup) => {
                     assert!(assign.src.borrow().is_constant(1, 1));
-                    let (beg, end) = Self::get_interval_from_guard(
+                    let name: calyx_ir::Id = sgroup.upgrade().borrow().name();
+                    for interval in Self::get_interval_from_guard(
                         &assign.guard,
                         target_group.borrow().get_latency(),
-                    );
-
-                    let name: calyx_ir::Id = sgroup.upgrade().borrow().name();
-                    children_vec.push((
-                        Self::build_dummy_tree(name, static_groups),
-                        (beg, end),
-                    ));
+                    ) {
+                        children_vec.push((
+                            Self::build_dummy_tree(name, static_groups),
+                            interval,
+                        ));
+                    }
                 }
             }
         }
@@ -716,6 +1355,7 @@ impl CompileStatic {
             root: (target_name, vec![]),
             fsm_schedule: BTreeMap::new(),
             children: children_vec,
+            branch_children: false,
             num_repeats: 1,
             num_states: target_group_ref.latency,
         })
@@ -749,11 +1389,37 @@ impl CompileStatic {
             .into_iter()
             .collect(),
             children: vec![],
+            branch_children: false,
             num_repeats: 1,
             num_states: target_group_ref.latency,
         })
     }
 
+    // Static groups may only trigger other static groups, since the trees
+    // built for them need to know the latency of every child. Returns an
+    // error naming both groups if a static group writes to a dynamic group's
+    // hole.
+    fn check_static_go_writes(
+        static_groups: &[ir::RRC<ir::StaticGroup>],
+    ) -> CalyxResult<()> {
+        for sgroup in static_groups {
+            let sgroup = sgroup.borrow();
+            for assign in &sgroup.assignments {
+                let dst = assign.dst.borrow();
+                if let PortParent::Group(group) = &dst.parent {
+                    let group_name = group.upgrade().borrow().name();
+                    return Err(Error::malformed_structure(format!(
+                        "static group `{}` writes to `{group_name}[{}]`, but `{group_name}` is not a static group. Static groups may only trigger other static groups",
+                        sgroup.name(),
+                        dst.name,
+                    ))
+                    .with_pos(&assign.attributes));
+                }
+            }
+        }
+        Ok(())
+    }
+
     /// Search through `static_groups` and get latency of sgroup named `name`
     fn get_sgroup_latency(
         name: ir::Id,
@@ -767,21 +1433,18 @@ impl CompileStatic {
             .get_latency()
     }
 
-    // Given a vec of tuples (i,j) sorted by the first element (i.e., `i`) checks
-    // whether the ranges do not overlap.
-    fn are_ranges_non_overlapping(ranges: &[(Node, (u64, u64))]) -> bool {
-        if ranges.is_empty() {
-            return true;
-        }
-        for i in 0..ranges.len() - 1 {
-            let (_, (_, end1)) = ranges[i];
-            let (_, (start2, _)) = ranges[i + 1];
-            // Ensure that the current range's end is less than or equal to the next range's start
-            if end1 > start2 {
-                return false;
-            }
-        }
-        true
+    // Given a vec of tuples (i,j) sorted by the first element (i.e., `i`),
+    // returns the first pair of adjacent ranges that overlap (if there is one).
+    fn find_overlapping_ranges(
+        ranges: &[(Node, (u64, u64))],
+    ) -> Option<((u64, u64), (u64, u64))> {
+        ranges.windows(2).find_map(|pair| {
+            let (_, (beg1, end1)) = pair[0];
+            let (_, (beg2, end2)) = pair[1];
+            // Ranges are sorted, so we only need to check the end of the
+            // first against the start of the second.
+            (end1 > beg2).then_some(((beg1, end1), (beg2, end2)))
+        })
     }
 
     // Get a vec of all static groups that were "enabled" in `ctrl`.
@@ -813,6 +1476,179 @@ impl CompileStatic {
             }
         }
     }
+
+    // Collects every static control statement in `ctrl` that is not a static
+    // enable. Nested static control is not collected, since the outermost
+    // statement already needs to be compiled away.
+    fn get_non_enable_static_ctrl<'a>(
+        ctrl: &'a ir::Control,
+        acc: &mut Vec<&'a ir::StaticControl>,
+    ) {
+        match ctrl {
+            ir::Control::Seq(ir::Seq { stmts, .. })
+            | ir::Control::Par(ir::Par { stmts, .. }) => {
+                for stmt in stmts {
+                    Self::get_non_enable_static_ctrl(stmt, acc);
+                }
+            }
+            ir::Control::Empty(_)
+            | ir::Control::Enable(_)
+            | ir::Control::Invoke(_) => (),
+            ir::Control::If(c) => {
+                Self::get_non_enable_static_ctrl(&c.tbranch, acc);
+                Self::get_non_enable_static_ctrl(&c.fbranch, acc);
+            }
+            ir::Control::Repeat(ir::Repeat { body, .. })
+            | ir::Control::While(ir::While { body, .. }) => {
+                Self::get_non_enable_static_ctrl(body, acc);
+            }
+            ir::Control::Static(ir::StaticControl::Enable(_)) => (),
+            ir::Control::Static(sc) => acc.push(sc),
+        }
+    }
+
+    // Replaces each `static if` in `ctrl` that `build_if_group` can lower with
+    // a static enable of the resulting group.
+    fn lower_static_ifs(ctrl: &mut ir::Control, builder: &mut ir::Builder) {
+        match ctrl {
+            ir::Control::Seq(ir::Seq { stmts, .. })
+            | ir::Control::Par(ir::Par { stmts, .. }) => {
+                for stmt in stmts {
+                    Self::lower_static_ifs(stmt, builder);
+                }
+            }
+            ir::Control::Empty(_)
+            | ir::Control::Enable(_)
+            | ir::Control::Invoke(_) => (),
+            ir::Control::If(c) => {
+                Self::lower_static_ifs(&mut c.tbranch, builder);
+                Self::lower_static_ifs(&mut c.fbranch, builder);
+            }
+            ir::Control::Repeat(ir::Repeat { body, .. })
+            | ir::Control::While(ir::While { body, .. }) => {
+                Self::lower_static_ifs(body, builder);
+            }
+            ir::Control::Static(ir::StaticControl::If(sif)) => {
+                if let Some(if_group) = Self::build_if_group(sif, builder) {
+                    *ctrl = ir::Control::static_enable(if_group);
+                }
+            }
+            ir::Control::Static(_) => (),
+        }
+    }
+
+    // Builds an `@if_ctrl` group for `sif` that enables the true branch when
+    // the condition holds and the false branch otherwise. Both branches share
+    // an FSM (see `get_coloring`), so this only works if they count the same
+    // way: they have to be enables of different groups that run for the
+    // entire latency of the `if` and don't enable any other static groups.
+    // Returns `None` if `sif` doesn't meet these requirements.
+    fn build_if_group(
+        sif: &ir::StaticIf,
+        builder: &mut ir::Builder,
+    ) -> Option<ir::RRC<ir::StaticGroup>> {
+        let (
+            ir::StaticControl::Enable(tbranch),
+            ir::StaticControl::Enable(fbranch),
+        ) = (&*sif.tbranch, &*sif.fbranch)
+        else {
+            return None;
+        };
+        let (tgroup, fgroup) =
+            (Rc::clone(&tbranch.group), Rc::clone(&fbranch.group));
+        let is_leaf_branch = |group: &ir::RRC<ir::StaticGroup>| {
+            let group = group.borrow();
+            group.get_latency() == sif.latency
+                && !group.assignments.iter().any(|assign| {
+                    matches!(
+                        assign.dst.borrow().parent,
+                        PortParent::StaticGroup(_)
+                    )
+                })
+        };
+        if tgroup.borrow().name() == fgroup.borrow().name()
+            || !is_leaf_branch(&tgroup)
+            || !is_leaf_branch(&fgroup)
+        {
+            return None;
+        }
+
+        let latency = sif.latency;
+        let if_group = builder.add_static_group("static_if", latency);
+        let port = Rc::clone(&sif.port);
+        let mut assigns = vec![];
+        // Same as static-inline: if the latency is 1, the branches can read
+        // the port directly. Otherwise, we have to save its value, since the
+        // branches might change it.
+        let cond_guard = if latency == 1 {
+            ir::Guard::port(port)
+        } else {
+            structure!( builder;
+                let cond = prim std_reg(port.borrow().width);
+                let cond_wire = prim std_wire(port.borrow().width);
+            );
+            assigns.extend(crate::passes::StaticInliner::make_cond_assigns(
+                cond,
+                Rc::clone(&cond_wire),
+                port,
+                latency,
+                builder,
+            ));
+            ir::Guard::port(cond_wire.borrow().get("out"))
+        };
+        let signal_on = builder.add_constant(1, 1);
+        assigns.push(builder.build_assignment(
+            tgroup.borrow().get("go"),
+            signal_on.borrow().get("out"),
+            cond_guard.clone(),
+        ));
+        assigns.push(builder.build_assignment(
+            fgroup.borrow().get("go"),
+            signal_on.borrow().get("out"),
+            cond_guard.not(),
+        ));
+        if_group.borrow_mut().assignments = assigns;
+        if_group.borrow_mut().attributes = sif.attributes.clone();
+        if_group
+            .borrow_mut()
+            .attributes
+            .insert(ir::BoolAttr::IfCtrl, 1);
+        Some(if_group)
+    }
+
+    // Returns a single error listing every non-enable static control statement
+    // in `ctrl`, if there are any. Running this before anything else means
+    // that a bad pass ordering is reported up front rather than panicking
+    // deep inside the compilation.
+    fn check_only_static_enables(ctrl: &ir::Control) -> CalyxResult<()> {
+        let mut bad_ctrl = vec![];
+        Self::get_non_enable_static_ctrl(ctrl, &mut bad_ctrl);
+        let Some((first, rest)) = bad_ctrl.split_first() else {
+            return Ok(());
+        };
+        let describe = |sc: &ir::StaticControl| match sc {
+            ir::StaticControl::Repeat(_) => "static repeat",
+            ir::StaticControl::Par(_) => "static par",
+            ir::StaticControl::Seq(_) => "static seq",
+            ir::StaticControl::If(_) => "static if",
+            ir::StaticControl::Empty(_) => "empty static control",
+            ir::StaticControl::Invoke(_) => "static invoke",
+            ir::StaticControl::Enable(_) => unreachable!(),
+        };
+        Err(Error::malformed_control(format!(
+            "found {} static control statement(s) that are not static enables, starting with this {}. Run {} before {} to compile them into static groups",
+            bad_ctrl.len(),
+            describe(first),
+            crate::passes::StaticInliner::name(),
+            Self::name(),
+        ))
+        .with_pos(first.get_attributes())
+        .with_annotations(
+            rest.iter().map(|sc| {
+                (sc.get_attributes().copy_span(), describe(sc).to_string())
+            }),
+        ))
+    }
 }
 
 // These are the functions used to compile for the static *component* interface,
@@ -877,7 +1713,7 @@ impl CompileStatic {
         builder: &mut ir::Builder,
         comp_sig: RRC<ir::Cell>,
     ) -> Vec<ir::Assignment<ir::Nothing>> {
-        let first_state_guard = fsm_tree.query_between((0, 1), builder);
+        let first_state_guard = fsm_tree.first_state_guard(builder);
         structure!(builder;
           let sig_reg = prim std_reg(1);
           let one = constant(1, 1);
@@ -977,9 +1813,11 @@ impl CompileStatic {
                 coloring,
                 colors_to_max_values,
                 colors_to_fsm,
-                self.one_hot_cutoff,
+                self.encoding_choice,
+                self.share_queries,
             );
-            fsm_tree.count_to_n(builder, Some(comp_go));
+            let reset_guard = self.fsm_reset_guard(builder)?;
+            fsm_tree.count_to_n(builder, Some(comp_go), reset_guard);
             fsm_tree.realize(
                 false,
                 static_groups,
@@ -1070,9 +1908,20 @@ impl Visitor for CompileStatic {
         sigs: &ir::LibrarySignatures,
         _comps: &[ir::Component],
     ) -> VisResult {
+        // Static ifs whose branches can share an FSM are compiled here.
+        // Everything below assumes that static-inline has already turned
+        // all other static control into static enables.
+        if !comp.is_static() {
+            let control = Rc::clone(&comp.control);
+            let mut builder = ir::Builder::new(comp, sigs);
+            Self::lower_static_ifs(&mut control.borrow_mut(), &mut builder);
+        }
+        Self::check_only_static_enables(&comp.control.borrow())?;
+
         // Drain static groups of component
         let mut sgroups: Vec<ir::RRC<ir::StaticGroup>> =
             comp.get_static_groups_mut().drain().collect();
+        Self::check_static_go_writes(&sgroups)?;
 
         let mut builder = ir::Builder::new(comp, sigs);
         // Get a vec of all groups that are enabled in comp's control.
@@ -1093,23 +1942,34 @@ impl Visitor for CompileStatic {
                     // but doesn't check the `ParCtrl` attribute. I think
                     // we could reduce code size by merging this function with
                     // `build_tree_object`.
-                    Self::build_dummy_tree(*id, &sgroups)
+                    Ok(Self::build_dummy_tree(*id, &sgroups))
                 }
             })
-            .collect_vec();
+            .collect::<CalyxResult<Vec<_>>>()?;
 
         // The first thing is to assign FSMs -> static islands.
         // We sometimes assign the same FSM to different static islands
         // to reduce register usage. We do this by getting greedy coloring.
         let coloring: HashMap<ir::Id, ir::Id> = self.get_coloring(
+            builder.component.name,
             &default_tree_objects,
             &sgroups,
             &mut builder.component.control.borrow_mut(),
-        );
+        )?;
         // We need the max_num_states  and max_num_repeats for each
         // color so we know how many bits the corresponding registers should get.
-        let colors_to_max_values =
+        let mut colors_to_max_values =
             Self::get_color_max_values(&coloring, &default_tree_objects);
+        if self.uniform_fsm_width {
+            Self::pad_color_max_values(&mut colors_to_max_values);
+        }
+        if self.print_coloring {
+            self.log_coloring(
+                builder.component.name,
+                &coloring,
+                &colors_to_max_values,
+            );
+        }
         let mut colors_to_fsms: HashMap<
             ir::Id,
             (OptionalStaticFSM, OptionalStaticFSM),
@@ -1132,6 +1992,11 @@ impl Visitor for CompileStatic {
             }
             simple_trees
         };
+        if let Some(out) = &mut self.dump_schedule {
+            for tree in &tree_objects {
+                Self::write_schedule(out, builder.component.name, tree)?;
+            }
+        }
 
         // Static components have a different interface than static groups.
         // If we have a static component, we have to compile the top-level
@@ -1179,9 +2044,11 @@ impl Visitor for CompileStatic {
                     &coloring,
                     &colors_to_max_values,
                     &mut colors_to_fsms,
-                    self.one_hot_cutoff,
+                    self.encoding_choice,
+                    self.share_queries,
                 );
-                tree.count_to_n(&mut builder, None);
+                let reset_guard = self.fsm_reset_guard(&builder)?;
+                tree.count_to_n(&mut builder, None, reset_guard);
                 tree.realize(
                     false,
                     &sgroups,
@@ -1193,6 +2060,10 @@ impl Visitor for CompileStatic {
             }
         }
 
+        if self.fsm_trace {
+            Self::add_fsm_trace_wires(&colors_to_fsms, &mut builder);
+        }
+
         // Rewrite static_group[go] to early_reset_group[go]
         // don't have to worry about writing static_group[done] b/c static
         // groups don't have done holes.
@@ -1346,7 +2217,12 @@ impl Visitor for CompileStatic {
         sigs: &ir::LibrarySignatures,
         _comps: &[ir::Component],
     ) -> VisResult {
-        if s.cond.is_none() {
+        // `build_wrapper_group_while` checks the condition port while the
+        // body is still running, which is only correct if the port is
+        // `@stable`. Otherwise, we fall back to the regular (extra-cycle)
+        // lowering, where the body is compiled like any other static island.
+        let stable_cond = s.port.borrow().has_attribute(ir::BoolAttr::Stable);
+        if s.cond.is_none() && stable_cond {
             if let ir::Control::Static(sc) = &mut *(s.body) {
                 let mut builder = ir::Builder::new(comp, sigs);
                 let reset_group_name = self.get_reset_group_name(sc);
@@ -1380,8 +2256,10 @@ impl Visitor for CompileStatic {
         //         unreachable!("Should have converted all static groups to dynamic. {} still has assignments in it. It's possible that you may need to run {} to remove dead groups and get rid of this error.", g.borrow().name(), crate::passes::DeadGroupRemoval::name());
         //     }
         // }
-        // remove all static groups
-        comp.get_static_groups_mut().retain(|_| false);
+        // remove all static groups, unless we want to keep them for debugging
+        if !self.keep_static_groups {
+            comp.get_static_groups_mut().retain(|_| false);
+        }
 
         // Remove control if static component
         if comp.is_static() {
@@ -1,12 +1,104 @@
 use std::collections::HashMap;
 
-use crate::traversal::{Action, ConstructVisitor, Named, VisResult, Visitor};
-use calyx_ir::{self as ir, BoolAttr, Guard, Id, Nothing, NumAttr};
-use calyx_utils::CalyxResult;
+use crate::traversal::{
+    Action, ConstructVisitor, Named, ParseVal, PassOpt, VisResult, Visitor,
+};
+use calyx_ir::{
+    self as ir, build_assignments, BoolAttr, Guard, Id, Nothing, NumAttr,
+};
+use calyx_utils::{CalyxResult, OutputFile};
+use serde::Serialize;
+
+/// Separates the components of a probe name.
+const DELIMITER: &str = "___";
+
+/// Bitwidth of the registers counting the active cycles of each group.
+const COUNTER_WIDTH: u64 = 32;
+
+/// Information about a single probe, serialized for profiling tools so that
+/// they don't have to parse probe names.
+#[derive(Serialize)]
+struct ProbeInfo {
+    /// Name of the probe cell
+    probe: String,
+    /// Kind of probe (`group`, `primitive`, `se`, or `cell`)
+    kind: &'static str,
+    /// The group, primitive, or cell whose activity the probe tracks
+    invoked: String,
+    /// The group that `invoked` is active in (`null` for group probes)
+    parent: Option<String>,
+    /// The component the probe lives in
+    component: String,
+}
 
 /// Adds probe wires to each group to detect when a group is active.
 /// Used by the profiler.
-pub struct ProfilerInstrumentation {}
+///
+/// Probes are marked `@protected`, which keeps them (and the assignments
+/// driving them) alive through `dead-cell-removal`, `dead-assign-removal`,
+/// `cell-share`, and `comb-prop`. Passes that do not know about `@protected`
+/// may still remove a probe because nothing reads it; the `keepalive` option
+/// guards against this by reading every probe from a continuous assignment.
+pub struct ProfilerInstrumentation {
+    /// Prefix prepended to the name of every probe cell.
+    probe_prefix: String,
+    /// Read every probe into a protected `_keepalive` wire.
+    keepalive: bool,
+    /// Add a register to each group counting the cycles it is active for.
+    cycle_counters: bool,
+    /// Where to write the map from probes to the constructs they track.
+    dump_probe_map: Option<OutputFile>,
+    /// Every probe added so far, across all components.
+    probes: Vec<ProbeInfo>,
+}
+
+impl ProfilerInstrumentation {
+    /// Returns the name of the probe cell of kind `kind` (one of `group`,
+    /// `primitive`, `se`, or `cell`) for `invoked`, which is active in
+    /// `parent` (if any) inside of component `comp`.
+    /// For example, `probe_name("", lt_reg, Some(cond), main, "primitive")`
+    /// is `lt_reg___cond___main_primitive_probe`.
+    pub fn probe_name(
+        prefix: &str,
+        invoked: Id,
+        parent: Option<Id>,
+        comp: Id,
+        kind: &str,
+    ) -> String {
+        match parent {
+            Some(parent) => format!(
+                "{prefix}{invoked}{DELIMITER}{parent}{DELIMITER}{comp}_{kind}_probe"
+            ),
+            None => format!("{prefix}{invoked}{DELIMITER}{comp}_{kind}_probe"),
+        }
+    }
+
+    /// Records that the probe `probe` of kind `kind` tracks `invoked`
+    /// (inside of `parent`, if any) in component `comp`.
+    fn record_probe(
+        &mut self,
+        probe: Id,
+        invoked: Id,
+        parent: Option<Id>,
+        comp: Id,
+        kind: &'static str,
+    ) {
+        self.probes.push(ProbeInfo {
+            probe: probe.to_string(),
+            kind,
+            invoked: invoked.to_string(),
+            parent: parent.map(|p| p.to_string()),
+            component: comp.to_string(),
+        });
+    }
+
+    /// Returns the name of the register counting the active cycles of
+    /// `group` inside of component `comp`. Its value can also be read from
+    /// the wire with the same name and an `_out` suffix.
+    pub fn counter_name(prefix: &str, group: Id, comp: Id) -> String {
+        format!("{prefix}{group}{DELIMITER}{comp}_group_counter")
+    }
+}
 
 impl Named for ProfilerInstrumentation {
     fn name() -> &'static str {
@@ -17,17 +109,49 @@ impl Named for ProfilerInstrumentation {
         "Add instrumentation for profiling"
     }
 
-    fn opts() -> Vec<crate::traversal::PassOpt> {
-        vec![]
+    fn opts() -> Vec<PassOpt> {
+        vec![
+            PassOpt::new(
+                "probe-prefix",
+                "Prefix prepended to the name of every probe cell",
+                ParseVal::String(String::new()),
+                PassOpt::parse_string,
+            ),
+            PassOpt::new(
+                "keepalive",
+                "Read every probe from a continuous assignment so it is not optimized away",
+                ParseVal::Bool(false),
+                PassOpt::parse_bool,
+            ),
+            PassOpt::new(
+                "cycle-counters",
+                "Add a register to each group counting the cycles it is active for",
+                ParseVal::Bool(false),
+                PassOpt::parse_bool,
+            ),
+            PassOpt::new(
+                "dump-probe-map",
+                "Write a JSON list of every probe with the group, primitive, or cell it tracks",
+                ParseVal::OutStream(OutputFile::Null),
+                PassOpt::parse_outstream,
+            ),
+        ]
     }
 }
 
 impl ConstructVisitor for ProfilerInstrumentation {
-    fn from(_ctx: &ir::Context) -> CalyxResult<Self>
+    fn from(ctx: &ir::Context) -> CalyxResult<Self>
     where
         Self: Sized + Named,
     {
-        Ok(ProfilerInstrumentation {})
+        let opts = Self::get_opts(ctx);
+        Ok(ProfilerInstrumentation {
+            probe_prefix: opts["probe-prefix"].string(),
+            keepalive: opts["keepalive"].bool(),
+            cycle_counters: opts["cycle-counters"].bool(),
+            dump_probe_map: opts["dump-probe-map"].not_null_outstream(),
+            probes: Vec::new(),
+        })
     }
 
     fn clear_data(&mut self) {}
@@ -40,7 +164,6 @@ impl Visitor for ProfilerInstrumentation {
         sigs: &ir::LibrarySignatures,
         _comps: &[ir::Component],
     ) -> VisResult {
-        let delimiter = "___";
         let mut acc = 0;
         let comp_name = comp.name;
         let mut structural_enable_map: HashMap<
@@ -142,11 +265,18 @@ impl Visitor for ProfilerInstrumentation {
             // probe and assignments for group (this group is currently active)
             for group_name in group_names.into_iter() {
                 // store group and component name (differentiate between groups of the same name under different components)
-                let name = format!(
-                    "{}{}{}_group_probe",
-                    group_name, delimiter, comp_name
+                let name = Self::probe_name(
+                    &self.probe_prefix,
+                    group_name,
+                    None,
+                    comp_name,
+                    "group",
                 );
                 let probe_cell = builder.add_primitive(name, "std_wire", &[1]);
+                // `add_primitive` may have renamed the cell to avoid a clash,
+                // so record the name the cell actually got.
+                let probe = probe_cell.borrow().name();
+                self.record_probe(probe, group_name, None, comp_name, "group");
                 let probe_asgn: ir::Assignment<Nothing> = builder
                     .build_assignment(
                         probe_cell.borrow().get("in"),
@@ -164,19 +294,26 @@ impl Visitor for ProfilerInstrumentation {
             // probe and assignments for primitive invocations (this group is activating a primitive)
             for (group, primitive_invs) in primitive_invoke_map.iter() {
                 for (primitive_cell_name, guard) in primitive_invs.iter() {
-                    let probe_cell_name = format!(
-                        "{}{}{}{}{}_primitive_probe",
-                        primitive_cell_name,
-                        delimiter,
-                        group,
-                        delimiter,
-                        comp_name
+                    let probe_cell_name = Self::probe_name(
+                        &self.probe_prefix,
+                        *primitive_cell_name,
+                        Some(*group),
+                        comp_name,
+                        "primitive",
                     );
                     let probe_cell = builder.add_primitive(
                         probe_cell_name,
                         "std_wire",
                         &[1],
                     );
+                    let probe = probe_cell.borrow().name();
+                    self.record_probe(
+                        probe,
+                        *primitive_cell_name,
+                        Some(*group),
+                        comp_name,
+                        "primitive",
+                    );
                     probe_cell.borrow_mut().add_attribute(BoolAttr::Control, 1);
                     probe_cell
                         .borrow_mut()
@@ -196,19 +333,26 @@ impl Visitor for ProfilerInstrumentation {
                 structural_enable_map.iter()
             {
                 for (parent_group, guard) in parent_groups.iter() {
-                    let probe_cell_name = format!(
-                        "{}{}{}{}{}_se_probe",
-                        invoked_group_name,
-                        delimiter,
-                        parent_group,
-                        delimiter,
-                        comp_name
+                    let probe_cell_name = Self::probe_name(
+                        &self.probe_prefix,
+                        *invoked_group_name,
+                        Some(*parent_group),
+                        comp_name,
+                        "se",
                     );
                     let probe_cell = builder.add_primitive(
                         probe_cell_name,
                         "std_wire",
                         &[1],
                     );
+                    let probe = probe_cell.borrow().name();
+                    self.record_probe(
+                        probe,
+                        *invoked_group_name,
+                        Some(*parent_group),
+                        comp_name,
+                        "se",
+                    );
                     probe_cell.borrow_mut().add_attribute(BoolAttr::Control, 1);
                     probe_cell
                         .borrow_mut()
@@ -229,19 +373,26 @@ impl Visitor for ProfilerInstrumentation {
             // probe cell and assignments for structural cell invocations (the group is structurally invoking a cell.)
             for (invoker_group, invoked_cells) in cell_invoke_map.iter() {
                 for invoked_cell in invoked_cells {
-                    let probe_cell_name = format!(
-                        "{}{}{}{}{}_cell_probe",
-                        invoked_cell,
-                        delimiter,
-                        invoker_group,
-                        delimiter,
-                        comp_name
+                    let probe_cell_name = Self::probe_name(
+                        &self.probe_prefix,
+                        *invoked_cell,
+                        Some(*invoker_group),
+                        comp_name,
+                        "cell",
                     );
                     let probe_cell = builder.add_primitive(
                         probe_cell_name,
                         "std_wire",
                         &[1],
                     );
+                    let probe = probe_cell.borrow().name();
+                    self.record_probe(
+                        probe,
+                        *invoked_cell,
+                        Some(*invoker_group),
+                        comp_name,
+                        "cell",
+                    );
                     probe_cell.borrow_mut().add_attribute(BoolAttr::Control, 1);
                     probe_cell
                         .borrow_mut()
@@ -262,14 +413,105 @@ impl Visitor for ProfilerInstrumentation {
             }
         }
         // ugh so ugly
+        let mut probe_cells =
+            Vec::with_capacity(group_name_assign_and_cell.len());
         for group in comp.groups.iter() {
             for (group_name, asgn, cell) in group_name_assign_and_cell.iter() {
                 if group.borrow().name() == group_name {
                     group.borrow_mut().assignments.push(asgn.clone());
                     comp.cells.add(cell.to_owned());
+                    probe_cells.push(cell.to_owned());
                 }
             }
         }
+        // count the cycles each group is active for. The counter's writes live
+        // in the group, so it increments exactly when the group's go is high.
+        if self.cycle_counters {
+            let groups = comp.groups.iter().cloned().collect::<Vec<_>>();
+            let mut builder = ir::Builder::new(comp, sigs);
+            for group in groups {
+                let name = Self::counter_name(
+                    &self.probe_prefix,
+                    group.borrow().name(),
+                    comp_name,
+                );
+                let counter = builder.add_primitive(
+                    name.clone(),
+                    "std_reg",
+                    &[COUNTER_WIDTH],
+                );
+                counter.borrow_mut().add_attribute(BoolAttr::Protected, 1);
+                let adder = builder.add_primitive(
+                    format!("{name}_adder"),
+                    "std_add",
+                    &[COUNTER_WIDTH],
+                );
+                let const_one = builder.add_constant(1, COUNTER_WIDTH);
+                let signal_on = builder.add_constant(1, 1);
+                let counter_asgns: [ir::Assignment<Nothing>; 4] = build_assignments!(
+                    builder;
+                    adder["left"] = ? counter["out"];
+                    adder["right"] = ? const_one["out"];
+                    counter["in"] = ? adder["out"];
+                    counter["write_en"] = ? signal_on["out"];
+                );
+                group.borrow_mut().assignments.extend(counter_asgns);
+                // expose the count on a protected @data wire that is always
+                // driven, so it can be read outside of the group as well.
+                let counter_out = builder.add_primitive(
+                    format!("{name}_out"),
+                    "std_wire",
+                    &[COUNTER_WIDTH],
+                );
+                counter_out
+                    .borrow_mut()
+                    .add_attribute(BoolAttr::Protected, 1);
+                counter_out.borrow_mut().add_attribute(BoolAttr::Data, 1);
+                let counter_out_asgn: ir::Assignment<Nothing> = builder
+                    .build_assignment(
+                        counter_out.borrow().get("in"),
+                        counter.borrow().get("out"),
+                        Guard::True,
+                    );
+                builder.add_continuous_assignments(vec![counter_out_asgn]);
+            }
+        }
+        // read every probe from a protected wire so that the probe's output is
+        // used even after the groups driving it have been compiled away.
+        if self.keepalive {
+            let mut builder = ir::Builder::new(comp, sigs);
+            for probe_cell in probe_cells {
+                let name = format!("{}_keepalive", probe_cell.borrow().name());
+                let keepalive_cell =
+                    builder.add_primitive(name, "std_wire", &[1]);
+                keepalive_cell
+                    .borrow_mut()
+                    .add_attribute(BoolAttr::Protected, 1);
+                let keepalive_asgn: ir::Assignment<Nothing> = builder
+                    .build_assignment(
+                        keepalive_cell.borrow().get("in"),
+                        probe_cell.borrow().get("out"),
+                        Guard::True,
+                    );
+                builder.add_continuous_assignments(vec![keepalive_asgn]);
+            }
+        }
+        Ok(Action::Continue)
+    }
+
+    /// If requested, emit the probe map after all components are processed
+    fn finish_context(&mut self, _ctx: &mut ir::Context) -> VisResult {
+        if let Some(json_out_file) = &mut self.dump_probe_map {
+            // The probes of a group are collected from hash maps, so sort them
+            // to keep the output deterministic.
+            self.probes.sort_by(|a, b| {
+                (&a.component, &a.probe).cmp(&(&b.component, &b.probe))
+            });
+            serde_json::to_writer_pretty(
+                json_out_file.get_write(),
+                &self.probes,
+            )?;
+        }
         Ok(Action::Continue)
     }
 }
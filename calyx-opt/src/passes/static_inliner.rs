@@ -107,7 +107,7 @@ impl StaticInliner {
     // makes the following assignments:
     // read more here: https://github.com/calyxir/calyx/issues/1344 (specifically
     // the section "Conditionl")
-    fn make_cond_assigns(
+    pub(super) fn make_cond_assigns(
         cond: ir::RRC<ir::Cell>,
         cond_wire: ir::RRC<ir::Cell>,
         port: ir::RRC<ir::Port>,
@@ -546,8 +546,9 @@ impl Visitor for WellFormed {
                             .with_pos(&assign.attributes))
                         } else if static_timing.get_interval().1 > group_latency {
                             Err(Error::malformed_structure(format!(
-                                "Static Timing Guard has interval `{}`, which is out of bounds since its static group has latency {}",
+                                "Static Timing Guard has interval `{}`, which is out of bounds since its static group `{}` has latency {}",
                                 static_timing,
+                                group.name(),
                                 group_latency
                             ))
                             .with_pos(&assign.attributes))
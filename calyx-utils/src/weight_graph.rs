@@ -129,30 +129,31 @@ where
 impl<T: Eq + Hash + ToString + Clone + Ord> Display for WeightGraph<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let rev_map = self.reverse_index();
-        let keys: Vec<_> = self.index_map.keys().collect();
-        let nodes = keys
-            .iter()
-            .map(|key| {
-                format!(
-                    "  {} [label=\"{}\"];",
-                    key.to_string(),
-                    key.to_string()
-                )
-            })
+        // Sort the nodes and edges by name so that the output is deterministic.
+        let nodes = self
+            .index_map
+            .keys()
+            .map(|key| key.to_string())
+            .sorted()
+            .map(|key| format!("  {} [label=\"{}\"];", key, key))
             .collect::<Vec<_>>()
             .join("\n");
         let edges = self
             .graph
             .edge_references()
             .map(|(a_idx, b_idx, _)| {
-                format!(
-                    "  {} -- {};",
-                    rev_map[&a_idx].to_string(),
-                    rev_map[&b_idx].to_string()
-                )
+                let a = rev_map[&a_idx].to_string();
+                let b = rev_map[&b_idx].to_string();
+                if a <= b {
+                    (a, b)
+                } else {
+                    (b, a)
+                }
             })
+            .sorted()
+            .map(|(a, b)| format!("  {} -- {};", a, b))
             .collect::<Vec<_>>()
             .join("\n");
-        write!(f, "graph {{ \n{}\n{}\n }}", nodes, edges)
+        write!(f, "graph {{\n{}\n{}\n}}", nodes, edges)
     }
 }
@@ -1,13 +1,12 @@
-use crate::error::AdapterResult;
-use baa::BitVecOps;
+use crate::condition::{BreakpointCondition, Operand};
+use crate::error::{AdapterResult, MyAdapterError};
+use baa::{BitVecOps, BitVecValue};
 use cider::debugger::commands::ParsedGroupName;
 use cider::debugger::source::structures::NewSourceMap;
 use cider::debugger::{OwnedDebugger, StoppedReason};
 use cider::flatten::flat_ir::base::{GlobalCellIdx, PortValue};
-use dap::events::{Event, OutputEventBody, StoppedEventBody};
 use dap::types::{
-    self, Breakpoint, Scope, Source, SourceBreakpoint, StackFrame, Thread,
-    Variable,
+    Breakpoint, Scope, Source, SourceBreakpoint, StackFrame, Thread, Variable,
 };
 use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
@@ -19,12 +18,16 @@ pub struct MyAdapter {
     thread_count: Counter,
     stack_count: Counter,
     breakpoints: HashSet<i64>,
+    conditions: HashMap<(String, String), BreakpointCondition>, // conditions of the breakpoints on each group
     stack_frames: Vec<StackFrame>,
+    group_frames: Vec<StackFrame>, // one frame per group active after the last step
+    active_groups: HashSet<(String, String)>,
     threads: Vec<Thread>, // This field is a placeholder
     object_references: HashMap<i64, Vec<(String, PortValue)>>,
     source: String,
     ids: NewSourceMap,
     frames_to_cmpts: HashMap<i64, GlobalCellIdx>, //stores mapping from frame ids to component idx
+    radix: Radix, // how port values are displayed unless a request asks otherwise
 }
 
 impl MyAdapter {
@@ -37,12 +40,16 @@ impl MyAdapter {
             thread_count: Counter::new(),
             stack_count: Counter::new(),
             breakpoints: HashSet::new(),
+            conditions: HashMap::new(),
             stack_frames: Vec::new(),
+            group_frames: Vec::new(),
+            active_groups: HashSet::new(),
             threads: Vec::new(),
             object_references: HashMap::new(),
             source: path.to_string(),
             ids: metadata,
             frames_to_cmpts: HashMap::new(),
+            radix: Radix::default(),
         })
     }
     /// function to deal with setting breakpoints and updating debugger accordingly
@@ -63,33 +70,62 @@ impl MyAdapter {
             (to_set, to_delete)
         }
 
+        // parse conditions first; breakpoints with invalid conditions are not
+        // set at all
+        let conditions = points
+            .iter()
+            .map(|p| {
+                p.condition
+                    .as_deref()
+                    .filter(|c| !c.trim().is_empty())
+                    .map(|c| c.parse::<BreakpointCondition>())
+                    .transpose()
+            })
+            .collect::<Vec<_>>();
+
         //check diffs
         let mut new_point_set = HashSet::new();
-        for p in points {
-            new_point_set.insert(p.line);
+        for (p, condition) in points.iter().zip(&conditions) {
+            if condition.is_ok() {
+                new_point_set.insert(p.line);
+            }
         }
         let (to_set, to_delete) = calc_diffs(&new_point_set, &self.breakpoints);
 
         //update adapter
         self.breakpoints.clear();
+        self.conditions.clear();
 
         let mut to_debugger_set: Vec<ParsedGroupName> = vec![];
         let mut to_client: Vec<Breakpoint> = vec![];
 
         // iterate over points received in request
-        for source_point in points {
-            self.breakpoints.insert(source_point.line);
+        for (source_point, condition) in points.iter().zip(conditions) {
             let name = self.ids.lookup_line(source_point.line as u64);
 
-            let breakpoint = make_breakpoint(
+            let mut breakpoint = make_breakpoint(
                 Some(source_point.line),
                 name.is_some(),
                 Some(path.clone()),
                 Some(source_point.line),
             );
+            let condition = match condition {
+                Ok(condition) => condition,
+                Err(e) => {
+                    breakpoint.verified = false;
+                    breakpoint.message = Some(e.to_string());
+                    to_client.push(breakpoint);
+                    continue;
+                }
+            };
             to_client.push(breakpoint);
+            self.breakpoints.insert(source_point.line);
 
             if let Some((component, group)) = name {
+                if let Some(condition) = condition {
+                    self.conditions
+                        .insert((component.clone(), group.clone()), condition);
+                }
                 if to_set.contains(&source_point.line) {
                     to_debugger_set.push(ParsedGroupName::from_comp_and_group(
                         component.clone(),
@@ -137,98 +173,189 @@ impl MyAdapter {
         self.threads.clone()
     }
 
-    /// returns all frames (components) in program
+    /// returns a frame for each currently active group, followed by all frames
+    /// (components) in program
     pub fn get_stack(&mut self) -> Vec<StackFrame> {
         if self.stack_frames.is_empty() {
             self.create_stack();
         }
-        self.stack_frames.clone()
+        self.group_frames
+            .iter()
+            .chain(self.stack_frames.iter())
+            .cloned()
+            .collect()
     }
 
-    /// creates call stack where each frame is a component. Adds frames to current
-    /// call stack
+    /// creates the call stack for when nothing is running yet, which only
+    /// holds the root component
     fn create_stack(&mut self) {
-        let components = self.debugger.get_components();
-        //turn the names into stack frames, ignore lines for right now
-        for (idx, comp) in components {
-            let frame = StackFrame {
-                id: self.stack_count.increment(),
-                // Maybe automate the name in the future?
-                name: String::from(comp),
-                source: Some(Source {
-                    name: None,
-                    path: Some(self.source.clone()),
-                    source_reference: None,
-                    presentation_hint: None,
-                    origin: None,
-                    sources: None,
-                    adapter_data: None,
-                    checksums: None,
-                }),
-                line: 1, // need to get this to be line component starts on
-                column: 0,
-                end_line: None,
-                end_column: None,
-                can_restart: None,
-                instruction_pointer_reference: None,
-                module_id: None,
+        let root = self
+            .debugger
+            .get_components()
+            .next()
+            .map(|(idx, comp)| (idx, comp.clone()));
+        if let Some((idx, comp)) = root {
+            // need to get this to be line component starts on
+            let frame = self.make_frame(comp, 1);
+            self.frames_to_cmpts.insert(frame.id, idx);
+            self.stack_frames.push(frame);
+        }
+    }
+
+    /// creates a stack frame with a fresh id pointing at `line` of the source
+    fn make_frame(&mut self, name: String, line: i64) -> StackFrame {
+        StackFrame {
+            id: self.stack_count.increment(),
+            name,
+            source: Some(Source {
+                name: None,
+                path: Some(self.source.clone()),
+                source_reference: None,
                 presentation_hint: None,
-            };
+                origin: None,
+                sources: None,
+                adapter_data: None,
+                checksums: None,
+            }),
+            line,
+            column: 0,
+            end_line: None,
+            end_column: None,
+            can_restart: None,
+            instruction_pointer_reference: None,
+            module_id: None,
+            presentation_hint: None,
+        }
+    }
+
+    /// rebuilds the call stack for the groups in `active`. Each group gets a
+    /// frame, ordered by line, so that every group running in parallel shows
+    /// up in the call stack. Below them is one frame per component instance
+    /// that encloses any of those groups, innermost first, so that invoking a
+    /// component pushes a frame for it and returning from it pops the frame.
+    fn update_frames(&mut self, active: &HashSet<(String, String)>) {
+        for frame in self
+            .group_frames
+            .drain(..)
+            .chain(self.stack_frames.drain(..))
+        {
+            self.frames_to_cmpts.remove(&frame.id);
+        }
+        let stacks = self
+            .debugger
+            .get_running_group_stacks()
+            .into_iter()
+            .filter(|(key, _)| active.contains(key))
+            .collect::<Vec<_>>();
+
+        let mut groups = stacks
+            .iter()
+            .filter_map(|(key, stack)| {
+                let line = self.ids.lookup(key)?.start_line;
+                let (idx, _) = stack.last()?;
+                Some((line, key.clone(), *idx))
+            })
+            .collect::<Vec<_>>();
+        groups.sort_by(|a, b| (a.0, &a.1).cmp(&(b.0, &b.1)));
+        for (line, (component, group), idx) in groups {
+            let frame =
+                self.make_frame(format!("{component}::{group}"), line as i64);
+            self.frames_to_cmpts.insert(frame.id, idx);
+            self.group_frames.push(frame);
+        }
+
+        // Each enclosing instance once, along with how deeply it is nested
+        let mut instances: Vec<(usize, String, GlobalCellIdx)> = vec![];
+        for (_, stack) in stacks {
+            for (depth, (idx, name)) in stack.into_iter().enumerate() {
+                if !instances.iter().any(|(_, other, _)| *other == name) {
+                    instances.push((depth, name, idx));
+                }
+            }
+        }
+        instances.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+        for (_, name, idx) in instances {
+            let frame = self.make_frame(name, 1);
             self.frames_to_cmpts.insert(frame.id, idx);
             self.stack_frames.push(frame);
         }
+        if self.stack_frames.is_empty() {
+            self.create_stack();
+        }
+    }
+
+    /// Steps the debugger a single time, which enters any control nested
+    /// inside of the currently running groups.
+    pub fn step_into(&mut self) -> AdapterResult<AdapterStepStatus> {
+        self.step(false)
     }
 
-    pub fn next_line(&mut self, _thread: i64) -> bool {
+    /// Steps the debugger until the set of running groups changes, so that
+    /// the control nested inside of the current groups is skipped over.
+    ///
+    /// Note that there is no way to step backwards: cider does not record
+    /// previous states of the program, so the adapter does not advertise
+    /// `supports_step_back`.
+    pub fn step_over(&mut self) -> AdapterResult<AdapterStepStatus> {
+        self.step(true)
+    }
+
+    /// Steps the debugger once, or, if `over` is set, until the running
+    /// groups change. Updates the group frames to the new running groups.
+    fn step(&mut self, over: bool) -> AdapterResult<AdapterStepStatus> {
         self.object_references.clear();
-        //return a more informative enum
-        // Step through once
-        let status = self.debugger.step(1).unwrap(); //need to unwrap a different way
-
-        // Check if done:
-        if status.get_done() {
-            // Give bool to exit the debugger
-            true
-        } else {
-            let map = status.get_status();
-            let mut line_number = 0;
-            // Implemented for loop for when more than 1 group is running,
-            // the code for now goes to the line of the last group running in the map, should deal
-            // with this in the future for when groups run in parallel.
-            for id in map {
-                let value = self.ids.lookup(id).unwrap().start_line;
-                line_number = value;
+        loop {
+            let status = self
+                .debugger
+                .step(1)
+                .map_err(|e| MyAdapterError::DebuggerError(e.to_string()))?;
+            if status.get_done() {
+                return Ok(AdapterStepStatus::Done);
+            }
+            if over && status.get_status() == &self.active_groups {
+                continue;
             }
-            // Set line of the stack frame and tell debugger we're not finished.
-            self.stack_frames[0].line = line_number as i64;
-            false
+            // Report every running group, not just one of them, so that
+            // stepping through a par shows all of its active children.
+            let active = status.get_status().clone();
+            self.update_frames(&active);
+            let mut groups = active.iter().cloned().collect::<Vec<_>>();
+            groups.sort();
+            self.active_groups = active;
+            let line = self.group_frames.first().map_or(0, |f| f.line);
+            return Ok(AdapterStepStatus::Paused { line, groups });
         }
     }
 
-    //display ports of each cell
-    pub fn get_variables(&self, var_ref: i64) -> Vec<Variable> {
+    /// Sets the radix port values are displayed in by default.
+    pub fn set_radix(&mut self, radix: Radix) {
+        self.radix = radix;
+    }
+
+    //display ports of each cell, in `radix` if given and the default radix
+    //otherwise
+    pub fn get_variables(
+        &self,
+        var_ref: i64,
+        radix: Option<Radix>,
+    ) -> Vec<Variable> {
+        let radix = radix.unwrap_or(self.radix);
         let ports = self.object_references.get(&var_ref);
         match ports {
             None => Vec::default(),
             Some(p) => {
                 let out: Vec<Variable> = p
                     .iter()
-                    .map(|(nam, val)| {
-                        let valu = val
-                            .as_option()
-                            .map(|x| x.val().to_u64().unwrap())
-                            .unwrap_or_default();
-                        Variable {
-                            name: String::from(nam),
-                            value: valu.to_string(),
-                            type_field: None,
-                            presentation_hint: None,
-                            evaluate_name: None,
-                            variables_reference: 0,
-                            named_variables: None,
-                            indexed_variables: None,
-                            memory_reference: None,
-                        }
+                    .map(|(nam, val)| Variable {
+                        name: String::from(nam),
+                        value: format_value(val, radix),
+                        type_field: None,
+                        presentation_hint: None,
+                        evaluate_name: None,
+                        variables_reference: 0,
+                        named_variables: None,
+                        indexed_variables: None,
+                        memory_reference: None,
                     })
                     .collect();
                 out
@@ -265,59 +392,199 @@ impl MyAdapter {
         out_vec
     }
 
+    /// Evaluates `expr` for the client (e.g., when hovering over a name or
+    /// watching it). `expr` can name a group, optionally qualified as
+    /// `component::group`, in which case the result says whether the group is
+    /// running, or a cell or cell port, in which case the result holds the
+    /// current values of its ports. Cells may be given by their full name
+    /// (`main.r`) or by any suffix of it (`r`), and ports as `r.out`.
+    pub fn evaluate(&self, expr: &str) -> AdapterResult<String> {
+        let expr = expr.trim();
+        let group = match expr.split_once("::") {
+            Some((comp, group)) => Some((comp.to_string(), group.to_string())),
+            None => self
+                .debugger
+                .get_components()
+                .map(|(_, comp)| (comp.clone(), expr.to_string()))
+                .find(|key| self.ids.lookup(key).is_some()),
+        };
+        if let Some(key) = group {
+            if let Some(contents) = self.ids.lookup(&key) {
+                let state = if self.active_groups.contains(&key) {
+                    "running"
+                } else {
+                    "not running"
+                };
+                return Ok(format!(
+                    "{}::{} (line {}): {state}",
+                    key.0, key.1, contents.start_line
+                ));
+            }
+        }
+
+        // The whole expression names a cell
+        if let Some((_, ports)) = self.find_cells(expr).first() {
+            return Ok(ports
+                .iter()
+                .map(|(port, val)| {
+                    format!("{port} = {}", format_value(val, self.radix))
+                })
+                .collect::<Vec<_>>()
+                .join(", "));
+        }
+        // The expression names a port of a cell
+        if let Some(val) = self.lookup_port(expr) {
+            return Ok(format_value(&val, self.radix));
+        }
+        Err(MyAdapterError::EvaluateError(expr.to_string()))
+    }
+
+    /// Returns the cells, along with their ports, whose full name is `name`
+    /// or ends with `.name`.
+    fn find_cells(
+        &self,
+        name: &str,
+    ) -> Vec<(String, Vec<(String, PortValue)>)> {
+        let suffix = format!(".{name}");
+        self.debugger
+            .get_components()
+            .flat_map(|(idx, _)| self.debugger.get_comp_cells(idx))
+            .filter(|(cell, _)| cell == name || cell.ends_with(&suffix))
+            .collect()
+    }
+
+    /// Returns the value of the port named by `name`, given as `cell.port`
+    /// where `cell` is found as in [Self::find_cells].
+    fn lookup_port(&self, name: &str) -> Option<PortValue> {
+        let (cell, port) = name.rsplit_once('.')?;
+        self.find_cells(cell)
+            .into_iter()
+            .flat_map(|(_, ports)| ports)
+            .find(|(p, _)| p == port)
+            .map(|(_, val)| val)
+    }
+
+    /// Whether the program should stop at the breakpoint on `group`. This is
+    /// the case if the breakpoint has no condition or its condition holds.
+    /// Conditions on undefined ports do not hold.
+    fn should_stop(&self, group: &(String, String)) -> bool {
+        let Some(condition) = self.conditions.get(group) else {
+            return true;
+        };
+        let value = |operand: &Operand| match operand {
+            Operand::Port(name) => {
+                let val = self.lookup_port(name)?;
+                Some(val.as_option()?.val().to_big_uint())
+            }
+            Operand::Literal(lit) => {
+                Some(BitVecValue::from_u64(*lit, 64).to_big_uint())
+            }
+        };
+        match (value(&condition.left), value(&condition.right)) {
+            (Some(left), Some(right)) => condition.op.holds(&left, &right),
+            _ => false,
+        }
+    }
+
     pub fn on_pause(&mut self) {
         //self.debugger.pause();
         self.object_references.clear();
     }
 
-    pub fn on_continue(&mut self, thread_id: i64) -> Event {
-        dbg!("continue - adapter");
-        let result = self.debugger.cont();
-        match result {
-            // honestly not sure if this is right behavior, still unsure what an output event IS lol.
-            Err(e) => Event::Output(OutputEventBody {
-                category: Some(types::OutputEventCategory::Stderr),
-                output: e.to_string(),
-                group: Some(types::OutputEventGroup::Start),
-                variables_reference: None,
-                source: None,
-                line: None,
-                column: None,
-                data: None,
-            }),
-            Ok(reason) => match reason {
-                StoppedReason::Done => Event::Terminated(None),
+    /// Runs the debugger until it finishes, hits a breakpoint whose condition
+    /// holds, or is paused.
+    pub fn on_continue(&mut self) -> AdapterResult<AdapterStepStatus> {
+        self.object_references.clear();
+        loop {
+            let reason = self
+                .debugger
+                .cont()
+                .map_err(|e| MyAdapterError::DebuggerError(e.to_string()))?;
+            match reason {
+                StoppedReason::Done => return Ok(AdapterStepStatus::Done),
                 StoppedReason::Breakpoint(names) => {
-                    let bp_lines: Vec<i64> = names
+                    let names = names
                         .into_iter()
-                        .map(|x| self.ids.lookup(&x).unwrap().start_line as i64)
+                        .filter(|name| self.should_stop(name))
+                        .collect::<Vec<_>>();
+                    // every breakpoint hit had a false condition
+                    if names.is_empty() {
+                        continue;
+                    }
+                    // breakpoints are identified by the line they are set on
+                    let ids = names
+                        .iter()
+                        .filter_map(|x| {
+                            Some(self.ids.lookup(x)?.start_line as i64)
+                        })
                         .collect();
-                    dbg!(&bp_lines);
-                    //in map add adjusting stack frame lines
-                    Event::Stopped(StoppedEventBody {
-                        reason: types::StoppedEventReason::Breakpoint,
-                        description: Some(String::from("hit breakpoint")),
-                        thread_id: Some(thread_id),
-                        preserve_focus_hint: None,
-                        all_threads_stopped: Some(true),
-                        text: None,
-                        hit_breakpoint_ids: Some(bp_lines),
-                    })
+                    let active = names.into_iter().collect::<HashSet<_>>();
+                    self.update_frames(&active);
+                    self.active_groups = active;
+                    return Ok(AdapterStepStatus::BreakpointHit { ids });
                 }
-                StoppedReason::PauseReq => Event::Stopped(StoppedEventBody {
-                    reason: types::StoppedEventReason::Pause,
-                    description: Some(String::from("Paused")),
-                    thread_id: Some(thread_id),
-                    preserve_focus_hint: None,
-                    all_threads_stopped: Some(true),
-                    text: None,
-                    hit_breakpoint_ids: None,
-                }),
-            },
+                StoppedReason::PauseReq => {
+                    let mut groups =
+                        self.active_groups.iter().cloned().collect::<Vec<_>>();
+                    groups.sort();
+                    let line = self.group_frames.first().map_or(0, |f| f.line);
+                    return Ok(AdapterStepStatus::Paused { line, groups });
+                }
+            }
+        }
+    }
+}
+
+/// The state the program is left in after the adapter steps or continues it.
+pub enum AdapterStepStatus {
+    /// The program has finished executing.
+    Done,
+    /// The program is paused with `groups`, given as (component, group)
+    /// pairs, running. `line` is the first line any of them starts on.
+    Paused {
+        line: i64,
+        groups: Vec<(String, String)>,
+    },
+    /// The program stopped at the breakpoints with the given `ids`.
+    BreakpointHit { ids: Vec<i64> },
+}
+
+/// The radix the adapter displays port values in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Radix {
+    #[default]
+    Decimal,
+    Hex,
+}
+
+impl std::str::FromStr for Radix {
+    type Err = MyAdapterError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "decimal" | "dec" => Ok(Radix::Decimal),
+            "hex" | "hexadecimal" => Ok(Radix::Hex),
+            _ => Err(MyAdapterError::InvalidRadix(s.to_string())),
         }
     }
 }
 
+/// Formats the value of a port for the client in `radix`. Values of any
+/// width are supported. Undefined values are shown as 0.
+fn format_value(val: &PortValue, radix: Radix) -> String {
+    let Some(val) = val.as_option() else {
+        return match radix {
+            Radix::Decimal => "0".to_string(),
+            Radix::Hex => "0x0".to_string(),
+        };
+    };
+    let val = val.val().to_big_uint();
+    match radix {
+        Radix::Decimal => val.to_string(),
+        Radix::Hex => format!("{val:#x}"),
+    }
+}
+
 /// Simple struct used to keep an index of the breakpoints used.
 pub struct Counter {
     value: i64,
@@ -0,0 +1,104 @@
+use crate::error::MyAdapterError;
+use std::str::FromStr;
+
+/// One side of the comparison in a breakpoint condition.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operand {
+    /// A port, named the same way as in an evaluate request (e.g., `r.out`).
+    Port(String),
+    /// An unsigned integer literal, given in decimal or as `0x` hex.
+    Literal(u64),
+}
+
+impl FromStr for Operand {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err("missing operand".to_string());
+        }
+        if let Some(hex) = s.strip_prefix("0x") {
+            return u64::from_str_radix(hex, 16)
+                .map(Operand::Literal)
+                .map_err(|_| format!("`{s}` is not a valid hex literal"));
+        }
+        if s.starts_with(|c: char| c.is_ascii_digit()) {
+            return s
+                .parse()
+                .map(Operand::Literal)
+                .map_err(|_| format!("`{s}` is not a valid literal"));
+        }
+        if s.contains(char::is_whitespace) {
+            return Err(format!("`{s}` is not a port name"));
+        }
+        Ok(Operand::Port(s.to_string()))
+    }
+}
+
+/// The comparison a breakpoint condition makes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CmpOp {
+    Eq,
+    Neq,
+    Lt,
+    Leq,
+    Gt,
+    Geq,
+}
+
+impl CmpOp {
+    // Two character operators come first so that `<=` is not read as `<`.
+    const ALL: [(&'static str, CmpOp); 6] = [
+        ("==", CmpOp::Eq),
+        ("!=", CmpOp::Neq),
+        ("<=", CmpOp::Leq),
+        (">=", CmpOp::Geq),
+        ("<", CmpOp::Lt),
+        (">", CmpOp::Gt),
+    ];
+
+    /// Whether `left <op> right` holds.
+    pub fn holds<T: Ord>(&self, left: &T, right: &T) -> bool {
+        match self {
+            CmpOp::Eq => left == right,
+            CmpOp::Neq => left != right,
+            CmpOp::Lt => left < right,
+            CmpOp::Leq => left <= right,
+            CmpOp::Gt => left > right,
+            CmpOp::Geq => left >= right,
+        }
+    }
+}
+
+/// A condition that must hold for a breakpoint to stop the program, of the
+/// form `<operand> <op> <operand>` (e.g., `r.out == 5`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BreakpointCondition {
+    pub left: Operand,
+    pub op: CmpOp,
+    pub right: Operand,
+}
+
+impl FromStr for BreakpointCondition {
+    type Err = MyAdapterError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid =
+            |msg: String| MyAdapterError::InvalidCondition(s.to_string(), msg);
+        let (left, op, right) = CmpOp::ALL
+            .iter()
+            .find_map(|(sym, op)| {
+                let (left, right) = s.split_once(sym)?;
+                Some((left, *op, right))
+            })
+            .ok_or_else(|| {
+                invalid("expected one of ==, !=, <, <=, >, >=".to_string())
+            })?;
+        Ok(BreakpointCondition {
+            left: left.parse().map_err(invalid)?,
+            op,
+            right: right.parse().map_err(invalid)?,
+        })
+    }
+}
@@ -32,6 +32,22 @@ pub enum MyAdapterError {
     #[error("Missing request")]
     MissingRequest,
 
+    /// Represents an error reported by the cider debugger.
+    #[error("Debugger error: {0}")]
+    DebuggerError(String),
+
+    /// Represents an expression that does not name a group, cell, or port.
+    #[error("Unable to evaluate `{0}`: no group, cell, or port has this name")]
+    EvaluateError(String),
+
+    /// Represents an unknown radix for displaying values.
+    #[error("Invalid radix `{0}`. Valid values are `decimal` or `hex`")]
+    InvalidRadix(String),
+
+    /// Represents a breakpoint condition that cannot be parsed.
+    #[error("Invalid breakpoint condition `{0}`: {1}")]
+    InvalidCondition(String, String),
+
     /// Represents a server error.
     #[error(transparent)]
     ServerError(#[from] ServerError),
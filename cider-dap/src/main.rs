@@ -1,10 +1,14 @@
 mod adapter;
+mod condition;
 mod error;
 
-use adapter::MyAdapter;
-use dap::events::{ExitedEventBody, StoppedEventBody, ThreadEventBody};
+use adapter::{AdapterStepStatus, MyAdapter, Radix};
+use dap::events::{
+    ExitedEventBody, OutputEventBody, StoppedEventBody, ThreadEventBody,
+};
+use dap::requests::Request;
 use dap::responses::{
-    ContinueResponse, ScopesResponse, SetBreakpointsResponse,
+    ContinueResponse, EvaluateResponse, ScopesResponse, SetBreakpointsResponse,
     SetExceptionBreakpointsResponse, StackTraceResponse, ThreadsResponse,
 };
 use error::MyAdapterError;
@@ -108,6 +112,8 @@ where
                     // Not sure if we need it
                     // Make VSCode send disassemble request
                     supports_stepping_granularity: Some(true),
+                    supports_evaluate_for_hovers: Some(true),
+                    supports_conditional_breakpoints: Some(true),
                     ..Default::default()
                 }));
             server.respond(rsp)?;
@@ -148,6 +154,18 @@ where
     // Construct the adapter
     let mut adapter = MyAdapter::new(program_path, std_path)?;
 
+    // The launch configuration can pick how port values are displayed
+    if let Command::Launch(params) = &req.command {
+        let radix = params
+            .additional_data
+            .as_ref()
+            .and_then(|data| data.get("radix"))
+            .and_then(|radix| radix.as_str());
+        if let Some(radix) = radix {
+            adapter.set_radix(radix.parse()?);
+        }
+    }
+
     // one thread idk why but it works
     let thread = &adapter.create_thread(String::from("Main")); //does not seem as though this does anything
 
@@ -244,7 +262,20 @@ fn run_server<R: Read, W: Write>(
             Command::Continue(args) => {
                 // need to run debugger, ngl not really sure how to implement this functionality
                 // run debugger until breakpoint or paused -> maybe have a process to deal w running debugger?
-                let stopped = adapter.on_continue(args.thread_id);
+                let stopped = match adapter.on_continue() {
+                    Ok(status) => continue_event(status, args.thread_id),
+                    // honestly not sure if this is right behavior, still unsure what an output event IS lol.
+                    Err(e) => Event::Output(OutputEventBody {
+                        category: Some(types::OutputEventCategory::Stderr),
+                        output: e.to_string(),
+                        group: Some(types::OutputEventGroup::Start),
+                        variables_reference: None,
+                        source: None,
+                        line: None,
+                        column: None,
+                        data: None,
+                    }),
+                };
                 let rsp =
                     req.success(ResponseBody::Continue(ContinueResponse {
                         all_threads_continued: Some(true),
@@ -274,49 +305,43 @@ fn run_server<R: Read, W: Write>(
             }
             // Step over
             Command::Next(args) => {
-                // Move stack frame
-                // If done then disconnect
-                if adapter.next_line(args.thread_id) {
-                    let rsp = req.clone().success(ResponseBody::Disconnect);
-                    server.send_event(Event::Exited(ExitedEventBody {
-                        exit_code: 0,
-                    }))?;
-                    server.respond(rsp)?;
-
-                    // Exit
-                    info!(logger, "exited debugger");
-                    return Ok(());
-                }
-
                 // Get ID before rsp takes ownership
                 let thread_id = args.thread_id;
-                let rsp = req.success(ResponseBody::Next);
-                // Send response first
-                server.respond(rsp)?;
-                // Send event
-                let stopped = create_stopped(
-                    types::StoppedEventReason::Step,
-                    String::from("Continue"),
-                    thread_id,
-                    false,
-                );
-                server.send_event(stopped)?;
+                match adapter.step_over() {
+                    Ok(status) => {
+                        if respond_to_step(
+                            server,
+                            req,
+                            ResponseBody::Next,
+                            status,
+                            thread_id,
+                        )? {
+                            info!(logger, "exited debugger");
+                            return Ok(());
+                        }
+                    }
+                    Err(e) => server.respond(req.error(&e.to_string()))?,
+                }
             }
             // Step in
             Command::StepIn(args) => {
                 // Get ID before rsp takes ownership
                 let thread_id = args.thread_id;
-                // Send response first
-                let rsp = req.success(ResponseBody::StepIn);
-                server.respond(rsp)?;
-                // Send event
-                let stopped = create_stopped(
-                    types::StoppedEventReason::Step,
-                    String::from("Paused on step"),
-                    thread_id,
-                    false,
-                );
-                server.send_event(stopped)?;
+                match adapter.step_into() {
+                    Ok(status) => {
+                        if respond_to_step(
+                            server,
+                            req,
+                            ResponseBody::StepIn,
+                            status,
+                            thread_id,
+                        )? {
+                            info!(logger, "exited debugger");
+                            return Ok(());
+                        }
+                    }
+                    Err(e) => server.respond(req.error(&e.to_string()))?,
+                }
             }
             // Step out
             Command::StepOut(args) => {
@@ -343,12 +368,31 @@ fn run_server<R: Read, W: Write>(
             }
             Command::Variables(args) => {
                 let var_ref = args.variables_reference;
+                let radix = requested_radix(&args.format);
                 let rsp =
                     req.success(ResponseBody::Variables(VariablesResponse {
-                        variables: adapter.get_variables(var_ref),
+                        variables: adapter.get_variables(var_ref, radix),
                     }));
                 server.respond(rsp)?;
             }
+            // Look up the value of a group, cell, or port by name
+            Command::Evaluate(args) => {
+                let rsp = match adapter.evaluate(&args.expression) {
+                    Ok(result) => {
+                        req.success(ResponseBody::Evaluate(EvaluateResponse {
+                            result,
+                            type_field: None,
+                            presentation_hint: None,
+                            variables_reference: 0,
+                            named_variables: None,
+                            indexed_variables: None,
+                            memory_reference: None,
+                        }))
+                    }
+                    Err(e) => req.error(&e.to_string()),
+                };
+                server.respond(rsp)?;
+            }
 
             unknown_command => {
                 return Err(MyAdapterError::UnhandledCommandError(
@@ -359,6 +403,88 @@ fn run_server<R: Read, W: Write>(
     }
 }
 
+/// Helper function used to respond to a step request once the adapter has
+/// stepped to `status`. If the program is done, this exits the debugger and
+/// returns true.
+fn respond_to_step<R: Read, W: Write>(
+    server: &mut Server<R, W>,
+    req: Request,
+    body: ResponseBody,
+    status: AdapterStepStatus,
+    thread_id: i64,
+) -> AdapterResult<bool> {
+    match status {
+        AdapterStepStatus::Done => {
+            let rsp = req.success(ResponseBody::Disconnect);
+            server
+                .send_event(Event::Exited(ExitedEventBody { exit_code: 0 }))?;
+            server.respond(rsp)?;
+            Ok(true)
+        }
+        AdapterStepStatus::Paused { line, groups } => {
+            // Send response first
+            server.respond(req.success(body))?;
+            // Send event
+            let groups = groups
+                .iter()
+                .map(|(comp, group)| format!("{comp}::{group}"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let stopped = create_stopped(
+                types::StoppedEventReason::Step,
+                format!("Paused on line {line} in {groups}"),
+                thread_id,
+                false,
+            );
+            server.send_event(stopped)?;
+            Ok(false)
+        }
+        // A step that stops at a breakpoint is reported like a continue.
+        AdapterStepStatus::BreakpointHit { .. } => {
+            server.respond(req.success(body))?;
+            server.send_event(continue_event(status, thread_id))?;
+            Ok(false)
+        }
+    }
+}
+
+/// Helper function used to create the event sent once a continue request
+/// stops with `status`.
+fn continue_event(status: AdapterStepStatus, thread_id: i64) -> Event {
+    match status {
+        AdapterStepStatus::Done => Event::Terminated(None),
+        AdapterStepStatus::BreakpointHit { ids } => {
+            Event::Stopped(StoppedEventBody {
+                reason: types::StoppedEventReason::Breakpoint,
+                description: Some(String::from("hit breakpoint")),
+                thread_id: Some(thread_id),
+                preserve_focus_hint: None,
+                all_threads_stopped: Some(true),
+                text: None,
+                hit_breakpoint_ids: Some(ids),
+            })
+        }
+        AdapterStepStatus::Paused { .. } => Event::Stopped(StoppedEventBody {
+            reason: types::StoppedEventReason::Pause,
+            description: Some(String::from("Paused")),
+            thread_id: Some(thread_id),
+            preserve_focus_hint: None,
+            all_threads_stopped: Some(true),
+            text: None,
+            hit_breakpoint_ids: None,
+        }),
+    }
+}
+
+/// Helper function that returns the radix a request asks values to be
+/// formatted in, if any.
+fn requested_radix(format: &Option<types::ValueFormat>) -> Option<Radix> {
+    format.as_ref()?.hex.map(|hex| match hex {
+        true => Radix::Hex,
+        false => Radix::Decimal,
+    })
+}
+
 ///Helper function used to create a Stopped event
 fn create_stopped(
     reason: types::StoppedEventReason,
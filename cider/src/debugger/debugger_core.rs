@@ -159,6 +159,26 @@ impl<C: AsRef<Context> + Clone> Debugger<C> {
         (parent_name, group_name)
     }
 
+    /// Returns each currently running group, as a (component, group) pair,
+    /// along with the full names and indices of the component instances that
+    /// enclose it. The instances go from the root component down to the one
+    /// running the group.
+    pub fn get_running_group_stacks(
+        &self,
+    ) -> Vec<((String, String), Vec<(GlobalCellIdx, String)>)> {
+        let env = self.interpreter.env();
+        env.get_currently_running_groups_with_comp()
+            .map(|(comp, group)| {
+                let stack = env
+                    .get_instance_path(comp)
+                    .into_iter()
+                    .map(|idx| (idx, env.get_full_name(idx)))
+                    .collect();
+                (self.grp_idx_to_name(group), stack)
+            })
+            .collect()
+    }
+
     pub fn get_all_cells(
         &self,
     ) -> impl Iterator<Item = (String, Vec<(String, PortValue)>)> + '_ {
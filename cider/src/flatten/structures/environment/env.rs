@@ -755,13 +755,22 @@ impl<C: AsRef<Context> + Clone> Environment<C> {
     pub fn get_currently_running_groups(
         &self,
     ) -> impl Iterator<Item = GroupIdx> + '_ {
+        self.get_currently_running_groups_with_comp()
+            .map(|(_, group)| group)
+    }
+
+    /// Like [Self::get_currently_running_groups], but also returns the
+    /// component instance running each group.
+    pub fn get_currently_running_groups_with_comp(
+        &self,
+    ) -> impl Iterator<Item = (GlobalCellIdx, GroupIdx)> + '_ {
         self.pc.iter().filter_map(|(_, point)| {
             let node = &self.ctx.as_ref().primary[point.control_node_idx];
             match node {
                 ControlNode::Enable(x) => {
                     let comp_go = self.get_comp_go(point.comp).unwrap();
                     if self.ports[comp_go].as_bool().unwrap_or_default() {
-                        Some(x.group())
+                        Some((point.comp, x.group()))
                     } else {
                         None
                     }
@@ -771,6 +780,14 @@ impl<C: AsRef<Context> + Clone> Environment<C> {
         })
     }
 
+    /// Returns the component instances enclosing `cell`, starting from the
+    /// root and ending with `cell` itself.
+    pub fn get_instance_path(&self, cell: GlobalCellIdx) -> Vec<GlobalCellIdx> {
+        let mut path = self.get_parent_path_from_cell(cell).unwrap();
+        path.push(cell);
+        path
+    }
+
     /// Given a cell idx, return the component definition that this cell is an
     /// instance of. Return None if the cell is not a component instance.
     pub fn get_component_idx(
@@ -0,0 +1,776 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+use std::{error::Error, fmt::Display};
+
+#[derive(Debug)]
+pub struct ParseNumTypeError;
+
+impl fmt::Display for ParseNumTypeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid number type")
+    }
+}
+
+impl Error for ParseNumTypeError {}
+
+/// Errors that can occur while converting a value.
+#[derive(Debug)]
+pub enum ConversionError {
+    /// The value couldn't be parsed as the given type
+    Parse { value: String, num_type: NumType },
+    /// There is no conversion between the two types
+    Unsupported { from: NumType, to: NumType },
+}
+
+impl Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConversionError::Parse { value, num_type } => {
+                write!(f, "Failed to parse `{}` as {}", value, num_type)
+            }
+            ConversionError::Unsupported { from, to } => {
+                write!(f, "Conversion from {} to {} is not supported", from, to)
+            }
+        }
+    }
+}
+
+impl Error for ConversionError {}
+
+pub type Result<T> = std::result::Result<T, ConversionError>;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum NumType {
+    Binary,
+    Float,
+    Hex,
+    Fixed,
+    /// Detect the type of each line from its prefix
+    Auto,
+}
+
+impl Display for NumType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NumType::Binary => "binary",
+            NumType::Float => "float",
+            NumType::Hex => "hex",
+            NumType::Fixed => "fixed",
+            NumType::Auto => "auto",
+        }
+        .fmt(f)
+    }
+}
+
+impl FromStr for NumType {
+    type Err = ParseNumTypeError;
+
+    fn from_str(input: &str) -> std::result::Result<NumType, Self::Err> {
+        match input {
+            "binary" => Ok(NumType::Binary),
+            "float" => Ok(NumType::Float),
+            "hex" => Ok(NumType::Hex),
+            "fixed" => Ok(NumType::Fixed),
+            "auto" => Ok(NumType::Auto),
+            _ => Err(ParseNumTypeError),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum RoundMode {
+    Nearest,
+    Zero,
+    Up,
+    Down,
+}
+
+impl Display for RoundMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RoundMode::Nearest => "nearest",
+            RoundMode::Zero => "zero",
+            RoundMode::Up => "up",
+            RoundMode::Down => "down",
+        }
+        .fmt(f)
+    }
+}
+
+impl FromStr for RoundMode {
+    type Err = ParseNumTypeError;
+
+    fn from_str(input: &str) -> std::result::Result<RoundMode, Self::Err> {
+        match input {
+            "nearest" => Ok(RoundMode::Nearest),
+            "zero" => Ok(RoundMode::Zero),
+            "up" => Ok(RoundMode::Up),
+            "down" => Ok(RoundMode::Down),
+            _ => Err(ParseNumTypeError),
+        }
+    }
+}
+
+/// Returns the type of [line] based on its prefix (`0x` for hex, `0b` for
+/// binary, and float otherwise), along with the line without the prefix.
+pub fn detect_type(line: &str) -> (NumType, &str) {
+    if let Some(hex) = line.strip_prefix("0x") {
+        (NumType::Hex, hex)
+    } else if let Some(binary) = line.strip_prefix("0b") {
+        (NumType::Binary, binary)
+    } else {
+        (NumType::Float, line)
+    }
+}
+
+/// Converts a single [line] from type [convert_from] to type [convert_to].
+/// If [convert_from] is `auto`, the type of the line is detected from its
+/// prefix, and binary output is always padded to 32 bits without spaces so
+/// that every line of a mixed-radix file has the same width. Otherwise, lines
+/// that already have type [convert_to] are returned as-is (except that hex is
+/// always formatted like `binary_to_hex` does).
+///
+/// # Arguments
+///
+/// * `line` - A string slice containing the value to be converted.
+/// * `convert_from` - A `NumType` enum indicating the type of the input data.
+/// * `convert_to` - A `NumType` enum indicating the type of the output data.
+/// * `exponent` - An `i32` value used as the exponent for conversions involving fixed-point numbers.
+/// * `bits` - Whether binary to fixed-point conversions should use bit slicing.
+/// * `round` - The rounding mode used when converting decimal floats to binary.
+///
+/// # Returns
+///
+/// Returns the converted value, or an `Err` if the line can't be parsed or
+/// the conversion isn't supported.
+pub fn convert_line(
+    line: &str,
+    convert_from: NumType,
+    convert_to: NumType,
+    exponent: i32,
+    bits: bool,
+    round: RoundMode,
+) -> Result<String> {
+    let auto = convert_from == NumType::Auto;
+    let (convert_from, line) = match convert_from {
+        NumType::Auto => detect_type(line),
+        _ => (convert_from, line),
+    };
+    match (convert_from, convert_to) {
+        (NumType::Binary, NumType::Binary) if auto => {
+            Ok(format!("{:032b}", parse_u32(line, 2, NumType::Binary)?))
+        }
+        (NumType::Float, NumType::Binary) if auto => {
+            Ok(float_to_binary(line, round)?.replace(' ', ""))
+        }
+        (NumType::Hex, NumType::Binary) => hex_to_binary(line),
+        (NumType::Float, NumType::Binary) => float_to_binary(line, round),
+        (NumType::Fixed, NumType::Binary) => fixed_to_binary(line, exponent),
+        (NumType::Binary, NumType::Hex) => binary_to_hex(line),
+        (NumType::Binary, NumType::Float) => binary_to_float(line),
+        (NumType::Binary, NumType::Fixed) => {
+            if !bits {
+                binary_to_fixed(line, exponent)
+            } else {
+                binary_to_fixed_bit_slice(line, exponent)
+            }
+        }
+        (NumType::Hex, NumType::Hex) => {
+            // Normalize the formatting to match `binary_to_hex`
+            Ok(format_hex(parse_u32(line, 16, NumType::Hex)?))
+        }
+        (from, to) if from == to && from != NumType::Auto => {
+            Ok(line.to_string())
+        }
+        (from, to) => Err(ConversionError::Unsupported { from, to }),
+    }
+}
+
+/// Formats [to_format] properly for float values
+fn format_binary(to_format: u32) -> String {
+    let binary_str = format!("{:032b}", to_format);
+    format!(
+        "{} {} {}",
+        &binary_str[0..1], // Sign bit
+        &binary_str[1..9], // Exponent
+        &binary_str[9..]   // Significand
+    )
+}
+
+fn format_hex(to_format: u32) -> String {
+    format!("0x{:X}", to_format)
+}
+
+/// Parses [value] as a `u32` in base [radix], reporting failures as
+/// [num_type] values. Whitespace is ignored so that the output of
+/// `float_to_binary` can be parsed back.
+fn parse_u32(value: &str, radix: u32, num_type: NumType) -> Result<u32> {
+    let digits: String = value.chars().filter(|c| !c.is_whitespace()).collect();
+    u32::from_str_radix(&digits, radix).map_err(|_| ConversionError::Parse {
+        value: value.to_string(),
+        num_type,
+    })
+}
+
+/// Returns the smallest `f32` greater than [x]
+fn next_up(x: f32) -> f32 {
+    if x.is_nan() || x == f32::INFINITY {
+        x
+    } else if x == 0.0 {
+        f32::from_bits(1)
+    } else if x > 0.0 {
+        f32::from_bits(x.to_bits() + 1)
+    } else {
+        f32::from_bits(x.to_bits() - 1)
+    }
+}
+
+/// Returns the largest `f32` less than [x]
+fn next_down(x: f32) -> f32 {
+    -next_up(-x)
+}
+
+/// Multiplies the little-endian big integer [digits] by [mul] and adds [add].
+fn big_mul_add(digits: &mut Vec<u32>, mul: u32, add: u32) {
+    let mut carry = add as u64;
+    for digit in digits.iter_mut() {
+        let product = *digit as u64 * mul as u64 + carry;
+        *digit = product as u32;
+        carry = product >> 32;
+    }
+    if carry != 0 {
+        digits.push(carry as u32);
+    }
+}
+
+/// Compares two little-endian big integers.
+fn big_cmp(lhs: &[u32], rhs: &[u32]) -> Ordering {
+    let trim = |digits: &[u32]| {
+        let len = digits.iter().rposition(|d| *d != 0).map_or(0, |i| i + 1);
+        digits[..len].to_vec()
+    };
+    let (lhs, rhs) = (trim(lhs), trim(rhs));
+    lhs.len()
+        .cmp(&rhs.len())
+        .then_with(|| lhs.iter().rev().cmp(rhs.iter().rev()))
+}
+
+/// Compares the magnitude of the decimal number [float_string] with the
+/// magnitude of [candidate] exactly. Returns `None` if [float_string] isn't
+/// a plain decimal number (e.g., `inf` or `nan`).
+fn cmp_decimal_magnitude(
+    float_string: &str,
+    candidate: f32,
+) -> Option<Ordering> {
+    let unsigned = float_string.trim_start_matches(['+', '-']);
+    let (mantissa, exponent) = match unsigned.find(['e', 'E']) {
+        Some(i) => (&unsigned[..i], &unsigned[i + 1..]),
+        None => (unsigned, "0"),
+    };
+    let (int_part, frac_part) =
+        mantissa.split_once('.').unwrap_or((mantissa, ""));
+    if !int_part
+        .chars()
+        .chain(frac_part.chars())
+        .all(|c| c.is_ascii_digit())
+    {
+        return None;
+    }
+    // The value is `decimal * 10^exponent`.
+    let mut decimal = vec![0];
+    for c in int_part.chars().chain(frac_part.chars()) {
+        big_mul_add(&mut decimal, 10, c.to_digit(10)?);
+    }
+    let decimal_is_zero = decimal.iter().all(|d| *d == 0);
+    if decimal_is_zero || candidate == 0.0 {
+        return Some((!decimal_is_zero).cmp(&(candidate != 0.0)));
+    }
+    if candidate.is_infinite() {
+        return Some(Ordering::Less);
+    }
+    // If the exponent doesn't fit, the value is so far out of range that
+    // `candidate` must be zero or infinite, which we've already handled.
+    let exponent = exponent.parse::<i64>().ok()? - frac_part.len() as i64;
+
+    // The candidate is `significand * 2^power`.
+    let bits = candidate.to_bits();
+    let (biased, fraction) = ((bits >> 23) & 0xFF, bits & 0x7F_FFFF);
+    let (significand, power) = if biased == 0 {
+        (fraction, -149)
+    } else {
+        (fraction | 0x80_0000, biased as i64 - 150)
+    };
+
+    // Move the negative powers to the other side of the comparison.
+    let mut lhs = decimal;
+    let mut rhs = vec![significand];
+    for _ in 0..exponent.max(0) {
+        big_mul_add(&mut lhs, 10, 0);
+    }
+    for _ in 0..(-exponent).max(0) {
+        big_mul_add(&mut rhs, 10, 0);
+    }
+    for _ in 0..power.max(0) {
+        big_mul_add(&mut rhs, 2, 0);
+    }
+    for _ in 0..(-power).max(0) {
+        big_mul_add(&mut lhs, 2, 0);
+    }
+    Some(big_cmp(&lhs, &rhs))
+}
+
+/// Parses [float_string] as an `f32`, rounding according to [round] when the
+/// number isn't exactly representable.
+///
+/// The exact decimal value is compared against the nearest `f32` to find out
+/// which side of it the value lies on.
+fn parse_f32(
+    float_string: &str,
+    round: RoundMode,
+) -> std::result::Result<f32, std::num::ParseFloatError> {
+    let nearest = float_string.parse::<f32>()?;
+    if round == RoundMode::Nearest {
+        return Ok(nearest);
+    }
+    let Some(order) = cmp_decimal_magnitude(float_string, nearest) else {
+        return Ok(nearest);
+    };
+    let negative = float_string.starts_with('-');
+    let too_small = order
+        == if negative {
+            Ordering::Less
+        } else {
+            Ordering::Greater
+        };
+    let too_large = order
+        == if negative {
+            Ordering::Greater
+        } else {
+            Ordering::Less
+        };
+    Ok(match round {
+        RoundMode::Up if too_small => next_up(nearest),
+        RoundMode::Down if too_large => next_down(nearest),
+        RoundMode::Zero if !negative && too_large => next_down(nearest),
+        RoundMode::Zero if negative && too_small => next_up(nearest),
+        _ => nearest,
+    })
+}
+
+/// Converts a string representation of a floating-point number to its binary
+/// format.
+///
+/// This function takes a string slice representing a floating-point number,
+/// converts it to a 32-bit floating-point number (`f32`), then converts this
+/// number to its binary representation, formatted as a string.
+///
+/// # Arguments
+///
+/// * `float_string` - A string slice containing the floating-point number to be converted.
+/// * `round` - How to round the number when it isn't exactly representable as an `f32`.
+///
+/// # Returns
+///
+/// Returns the binary representation, or an `Err` if the input string cannot
+/// be parsed as a floating-point number.
+pub fn float_to_binary(float_string: &str, round: RoundMode) -> Result<String> {
+    // Convert string to float
+    let float_of_string =
+        parse_f32(float_string, round).map_err(|_| ConversionError::Parse {
+            value: float_string.to_string(),
+            num_type: NumType::Float,
+        })?;
+
+    // Convert float to binary
+    let binary_of_float = float_of_string.to_bits();
+    Ok(format_binary(binary_of_float))
+}
+
+/// Converts a string representation of a hexadecimal number to its binary
+/// format.
+///
+/// This function takes a string slice representing a hexadecimal number,
+/// converts it to a 32-bit integer (`u32`), then converts this number to its
+/// binary representation, formatted as a 32-bit string.
+///
+/// # Arguments
+///
+/// * `hex_string` - A string slice containing the hexadecimal number to be converted.
+///
+/// # Returns
+///
+/// Returns the binary representation, or an `Err` if the input string cannot
+/// be parsed as a hexadecimal number.
+pub fn hex_to_binary(hex_string: &str) -> Result<String> {
+    // Convert hex to binary
+    let binary_of_hex = parse_u32(hex_string, 16, NumType::Hex)?;
+
+    // Format nicely, padding to 32 bits like the other binary outputs
+    Ok(format!("{:032b}", binary_of_hex))
+}
+
+/// Converts a string representation of a binary number to its hexadecimal
+/// format.
+///
+/// This function takes a string slice representing a binary number,
+/// converts it to a 32-bit integer (`u32`), then converts this number to its
+/// hexadecimal representation, formatted as a string.
+///
+/// # Arguments
+///
+/// * `binary_string` - A string slice containing the binary number to be converted.
+///
+/// # Returns
+///
+/// Returns the hexadecimal representation, or an `Err` if the input string
+/// cannot be parsed as a binary number.
+pub fn binary_to_hex(binary_string: &str) -> Result<String> {
+    let hex_of_binary = parse_u32(binary_string, 2, NumType::Binary)?;
+
+    Ok(format_hex(hex_of_binary))
+}
+
+/// Converts a string representation of a binary number to its floating-point
+/// format.
+///
+/// This function takes a string slice representing a binary number,
+/// converts it to a 32-bit integer (`u32`), then interprets this integer as
+/// the binary representation of a 32-bit floating-point number (`f32`),
+/// formatted as a string.
+///
+/// # Arguments
+///
+/// * `binary_string` - A string slice containing the binary number to be converted.
+///
+/// # Returns
+///
+/// Returns the floating-point representation, or an `Err` if the input string
+/// cannot be parsed as a binary number.
+pub fn binary_to_float(binary_string: &str) -> Result<String> {
+    let binary_value = parse_u32(binary_string, 2, NumType::Binary)?;
+
+    // Interpret the integer as the binary representation of a floating-point number
+    let float_value = f32::from_bits(binary_value);
+
+    Ok(format!("{:?}", float_value))
+}
+
+/// Converts a string representation of a fixed-point number to its binary
+/// format.
+///
+/// This function takes a string slice representing a fixed-point number,
+/// multiplies it by 2 raised to the power of the negative exponent, converts the result
+/// to a 32-bit integer, and then to its binary representation, formatted as a string.
+///
+/// # Arguments
+///
+/// * `fixed_string` - A string slice containing the fixed-point number to be converted.
+/// * `exp_int` - The exponent to be applied in the conversion process.
+///
+/// # Returns
+///
+/// Returns the binary representation, or an `Err` if the input string cannot
+/// be parsed as a fixed-point number.
+pub fn fixed_to_binary(fixed_string: &str, exp_int: i32) -> Result<String> {
+    // Convert fixed value from string to int
+    let fixed_value =
+        fixed_string
+            .parse::<f32>()
+            .map_err(|_| ConversionError::Parse {
+                value: fixed_string.to_string(),
+                num_type: NumType::Fixed,
+            })?;
+
+    //exponent int to float so we can multiply
+    let exponent = exp_int as f32;
+
+    // Exponent math
+    let multiplied_fixed = fixed_value * 2_f32.powf(-exponent);
+
+    // Convert to a 32-bit integer
+    let multiplied_fixed_as_i32 = multiplied_fixed as i32;
+
+    // Convert to a binary string with 32 bits
+    Ok(format!("{:032b}", multiplied_fixed_as_i32))
+}
+
+/// Converts a string representation of a binary number to its fixed-point
+/// format.
+///
+/// This function takes a string slice representing a binary number,
+/// converts it to a 32-bit unsigned integer, interprets this integer as
+/// a floating-point number, divides it by 2 raised to the power of the negative exponent,
+/// and converts the result to its fixed-point representation, formatted as a string.
+///
+/// # Arguments
+///
+/// * `binary_string` - A string slice containing the binary number to be converted.
+/// * `exp_int` - The exponent to be applied in the conversion process.
+///
+/// # Returns
+///
+/// Returns the fixed-point representation, or an `Err` if the input string
+/// cannot be parsed as a binary number.
+pub fn binary_to_fixed(binary_string: &str, exp_int: i32) -> Result<String> {
+    // Convert binary value from string to int
+    let binary_value = parse_u32(binary_string, 2, NumType::Binary)?;
+
+    // Convert to fixed
+    let int_of_binary = binary_value as f32;
+
+    //exponent int to float so we can multiply
+    let exponent = exp_int as f32;
+
+    // Exponent math
+    let divided: f32 = int_of_binary / 2_f32.powf(-exponent);
+
+    Ok(format!("{:+.8e}", divided))
+}
+
+/// Like [binary_to_fixed], but moves the binary point by shifting the bits of
+/// the binary number instead of dividing.
+pub fn binary_to_fixed_bit_slice(
+    binary_string: &str,
+    exp_int: i32,
+) -> Result<String> {
+    // Convert binary string to an integer
+    let binary_int = parse_u32(binary_string, 2, NumType::Binary)?;
+
+    // Adjust the binary point based on the exponent
+    let mut result = binary_int;
+    if exp_int < 0 {
+        // If exponent is negative, shift right (multiply by 2^(-exp_int))
+        result >>= -exp_int as u32;
+    } else {
+        // If exponent is positive, shift left (multiply by 2^(exp_int))
+        result <<= exp_int as u32;
+    }
+
+    // Convert result to a fixed-point decimal representation
+    let fixed_value = result as f32;
+
+    Ok(format!("{:.8e}", fixed_value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic xorshift generator so the round-trip tests don't need
+    /// an extra dependency.
+    fn random_u32s(count: usize) -> impl Iterator<Item = u32> {
+        let mut state: u32 = 0x2545_F491;
+        (0..count).map(move |_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state
+        })
+    }
+
+    #[test]
+    fn float_to_binary_nearest() {
+        assert_eq!(
+            float_to_binary("1.5", RoundMode::Nearest).unwrap(),
+            "0 01111111 10000000000000000000000"
+        );
+        assert_eq!(
+            float_to_binary("0.1", RoundMode::Nearest).unwrap(),
+            "0 01111011 10011001100110011001101"
+        );
+    }
+
+    #[test]
+    fn float_to_binary_round_modes() {
+        let round = |s, mode| float_to_binary(s, mode).unwrap();
+        // 0.1 rounds up to the nearest f32, -0.1 rounds down
+        assert_eq!(
+            round("0.1", RoundMode::Zero),
+            "0 01111011 10011001100110011001100"
+        );
+        assert_eq!(
+            round("-0.1", RoundMode::Zero),
+            "1 01111011 10011001100110011001100"
+        );
+        assert_eq!(
+            round("0.1", RoundMode::Up),
+            round("0.1", RoundMode::Nearest)
+        );
+        assert_eq!(
+            round("0.1", RoundMode::Down),
+            round("0.1", RoundMode::Zero)
+        );
+        assert_eq!(
+            round("-0.1", RoundMode::Up),
+            round("-0.1", RoundMode::Zero)
+        );
+        // Exactly representable values are unaffected by the rounding mode
+        for mode in [RoundMode::Zero, RoundMode::Up, RoundMode::Down] {
+            assert_eq!(round("1.5", mode), round("1.5", RoundMode::Nearest));
+        }
+        // Values just above an f32 round up to the next one, even if they
+        // are too close to it to tell apart as an f64
+        assert_eq!(
+            round("1.00000000000000000001", RoundMode::Up),
+            "0 01111111 00000000000000000000001"
+        );
+        assert_eq!(
+            round("1.00000000000000000001", RoundMode::Down),
+            "0 01111111 00000000000000000000000"
+        );
+        assert_eq!(
+            round("-1.00000000000000000001", RoundMode::Up),
+            "1 01111111 00000000000000000000000"
+        );
+        assert_eq!(
+            round("-1.00000000000000000001", RoundMode::Down),
+            "1 01111111 00000000000000000000001"
+        );
+        // Values past f32::MAX only round to infinity when rounding up
+        assert_eq!(
+            round("3.4028236e38", RoundMode::Zero),
+            "0 11111110 11111111111111111111111"
+        );
+        assert_eq!(
+            round("3.4028236e38", RoundMode::Up),
+            "0 11111111 00000000000000000000000"
+        );
+    }
+
+    #[test]
+    fn float_to_binary_bad_input() {
+        assert!(matches!(
+            float_to_binary("one", RoundMode::Nearest),
+            Err(ConversionError::Parse {
+                num_type: NumType::Float,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn hex_to_binary_pads() {
+        assert_eq!(
+            hex_to_binary("1F").unwrap(),
+            "00000000000000000000000000011111"
+        );
+        assert_eq!(hex_to_binary("0").unwrap(), "0".repeat(32));
+        assert!(hex_to_binary("xyz").is_err());
+    }
+
+    #[test]
+    fn binary_to_hex_formats() {
+        assert_eq!(binary_to_hex("101").unwrap(), "0x5");
+        assert_eq!(binary_to_hex("11111111").unwrap(), "0xFF");
+        assert!(binary_to_hex("102").is_err());
+    }
+
+    #[test]
+    fn binary_to_float_formats() {
+        assert_eq!(
+            binary_to_float("00111111110000000000000000000000").unwrap(),
+            "1.5"
+        );
+        // The spaced output of float_to_binary is accepted too
+        assert_eq!(
+            binary_to_float("1 01111111 10000000000000000000000").unwrap(),
+            "-1.5"
+        );
+    }
+
+    #[test]
+    fn fixed_conversions() {
+        assert_eq!(
+            fixed_to_binary("1.5", -4).unwrap(),
+            "00000000000000000000000000011000"
+        );
+        assert_eq!(binary_to_fixed("11000", -4).unwrap(), "+1.50000000e0");
+        assert_eq!(
+            binary_to_fixed_bit_slice("11000", -4).unwrap(),
+            "1.00000000e0"
+        );
+        assert!(fixed_to_binary("1.5.1", -4).is_err());
+    }
+
+    #[test]
+    fn convert_line_auto() {
+        let convert = |line, to| {
+            convert_line(line, NumType::Auto, to, -1, false, RoundMode::Nearest)
+        };
+        assert_eq!(
+            convert("0x1F", NumType::Binary).unwrap(),
+            hex_to_binary("1F").unwrap()
+        );
+        assert_eq!(
+            convert("0b101", NumType::Binary).unwrap(),
+            "00000000000000000000000000000101"
+        );
+        assert_eq!(
+            convert("1.5", NumType::Binary).unwrap(),
+            "00111111110000000000000000000000"
+        );
+        assert_eq!(convert("0b101", NumType::Hex).unwrap(), "0x5");
+        assert_eq!(convert("0x1f", NumType::Hex).unwrap(), "0x1F");
+        assert!(matches!(
+            convert("0x1F", NumType::Float),
+            Err(ConversionError::Unsupported {
+                from: NumType::Hex,
+                to: NumType::Float
+            })
+        ));
+    }
+
+    #[test]
+    fn convert_line_auto_mixed_radix() {
+        let lines = ["0x1F", "0b101", "1.5", "0b0", "0xFFFFFFFF", "-2"];
+        let converted: Vec<String> = lines
+            .iter()
+            .map(|line| {
+                convert_line(
+                    line,
+                    NumType::Auto,
+                    NumType::Binary,
+                    -1,
+                    false,
+                    RoundMode::Nearest,
+                )
+                .unwrap()
+            })
+            .collect();
+        for binary in &converted {
+            assert_eq!(binary.len(), 32, "`{binary}` is not 32 bits wide");
+            assert!(binary.chars().all(|c| c == '0' || c == '1'));
+        }
+        // Each value still round-trips through its own radix
+        assert_eq!(u32::from_str_radix(&converted[0], 2).unwrap(), 0x1F);
+        assert_eq!(u32::from_str_radix(&converted[1], 2).unwrap(), 0b101);
+        assert_eq!(
+            u32::from_str_radix(&converted[5], 2).unwrap(),
+            (-2.0f32).to_bits()
+        );
+    }
+
+    #[test]
+    fn float_round_trip() {
+        for bits in random_u32s(10_000) {
+            let value = f32::from_bits(bits);
+            if value.is_nan() {
+                continue;
+            }
+            let float_string = format!("{:?}", value);
+            let binary =
+                float_to_binary(&float_string, RoundMode::Nearest).unwrap();
+            assert_eq!(binary_to_float(&binary).unwrap(), float_string);
+        }
+    }
+
+    #[test]
+    fn hex_round_trip() {
+        for value in random_u32s(10_000) {
+            let hex = format_hex(value);
+            let binary = hex_to_binary(&hex[2..]).unwrap();
+            assert_eq!(binary_to_hex(&binary).unwrap(), hex);
+        }
+    }
+}